@@ -53,6 +53,8 @@
 //!                         |             3
 //!                         |
 //!                         4
+//!
+//! Summary: 1 expectation, 0 passed, 1 failed, in 2.1µs
 //! ```
 //!
 //! Much better than using `assert`:
@@ -98,6 +100,18 @@
 //! ----------------------
 //! </pre>
 //!
+//! When comparing long texts, unchanged lines far from any change are collapsed, and the diff
+//! is split into hunks with line numbers, as in a unified diff. The number of unchanged lines
+//! shown around each change can be configured with `vinegar::vinegar::set_diff_options`, whose
+//! `vinegar::vinegar::DiffOptions` can also lay out both texts side by side, with
+//! `vinegar::vinegar::DiffLayout::SideBySide`, in columns fitting the width of the terminal.
+//!
+//! Huge values are shortened in failure messages: lines and values exceeding the configured
+//! limits are cut around their first difference, with a note saying how much was omitted.
+//!
+//! With the `serde_json` feature, `expect_json_eq` compares JSON values semantically and reports
+//! each difference with its JSON path.
+//!
 
 #![crate_name = "vinegar"]
 #![doc(html_root_url = "http://docs.rs/vinegar")]
@@ -106,6 +120,13 @@
 
 extern crate difference;
 extern crate ansi_term;
+extern crate unicode_segmentation;
+extern crate unicode_width;
+extern crate vinegar_macros;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "serde_json")]
+extern crate serde_json;
 
 /// The core module of the `vinegar` crate.
 #[macro_use]
@@ -114,19 +135,73 @@ pub mod vinegar;
 /// unit test module
 #[cfg(test)]
 mod tests {
-    use vinegar::check;
-    use ansi_term::Color::{Red, Green, White};
+    use vinegar::{check, check_each, check_parallel, diff_options, set_check_options, set_diff_options, with_fixture,
+                  CheckOptions, Cmd, DiffLayout, DiffOptions, Fixture, InlineDiff, Spy, TempDir};
+    #[cfg(unix)]
+    use vinegar::capture_output;
+    use ansi_term::Color::{Red, Green, White, Cyan};
+    use std::cell::Cell;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use std::fs;
+    use std::io::{self, Write};
+    use std::panic;
+    use std::time::Duration;
 
-    /// Compare strings after removing trailing whitespaces from all lines
-    fn assert_eq_multiline(left: &str, right: &str) {
-        let l = left.split('\n').map(|line| line.trim_right())
+    trait Repository {
+        fn find(&self, id: u32) -> Option<String>;
+        fn save(&mut self, id: u32, value: String);
+        fn clear(&mut self);
+    }
+
+    mock! {
+        MockRepository: Repository {
+            fn find(&self, id: u32) -> Option<String>;
+            fn save(&mut self, id: u32, value: String);
+            fn clear(&mut self);
+        }
+    }
+
+    thread_local! {
+        static TEARDOWNS: Cell<u32> = const { Cell::new(0) };
+    }
+
+    struct CountingFixture(u32);
+
+    impl Fixture for CountingFixture {
+        fn setup() -> CountingFixture {
+            CountingFixture(0)
+        }
+
+        fn teardown(self) {
+            TEARDOWNS.with(|teardowns| teardowns.set(teardowns.get() + 1));
+        }
+    }
+
+    /// Remove trailing whitespaces from all lines
+    fn trim_lines(text: &str) -> String {
+        text.split('\n').map(|line| line.trim_end())
             .collect::<Vec<_>>()
-            .join("\n");
-        let r = right.split('\n').map(|line| line.trim_right())
+            .join("\n")
+    }
+
+    /// The difference section of an error message
+    fn difference_of(msg: &str) -> &str {
+        &msg[msg.find("----- Difference -----").expect("no difference in message")..]
+    }
+
+    /// Text with numbered lines from `1` to `count`, replacing the given lines
+    fn numbered_lines(count: usize, replacements: &[(usize, &str)]) -> String {
+        (1..count + 1).map(|n| replacements.iter()
+            .find(|&&(line, _)| line == n)
+            .map(|&(_, text)| text.to_owned())
+            .unwrap_or_else(|| format!("line {}", n)))
             .collect::<Vec<_>>()
-            .join("\n");
+            .join("\n")
+    }
 
-        assert_eq!(l, r);
+    /// Compare strings after removing trailing whitespaces from all lines
+    fn assert_eq_multiline(left: &str, right: &str) {
+        assert_eq!(trim_lines(left), trim_lines(right));
     }
 
     #[test]
@@ -144,7 +219,7 @@ mod tests {
         check(examples.iter().map(|&ex| expect!(ex > 0)));
 
         // example-based test with both input and assertion as examples
-        let examples = vec![
+        let examples = [
             // (input, expected result)
             (1, 2),
             (2, 4),
@@ -294,10 +369,11 @@ mod tests {
                          |
                          hello
 ----- Difference -----
-{}{}
-{}{}
-----------------------\n", Red.paint("-"), White.on(Red).paint("hello"),
-                                         Green.paint("+"), White.on(Green).paint("hevvo")), &msg);
+{}{}{}{}
+{}{}{}{}
+----------------------\n", Red.paint("-"), Red.paint("he"), White.on(Red).paint("ll"), Red.paint("o"),
+                                         Green.paint("+"), Green.paint("he"), White.on(Green).paint("vv"),
+                                         Green.paint("o")), &msg);
         } else {
             panic!("Should have failed");
         }
@@ -400,4 +476,1032 @@ mod tests {
             panic!("Should have failed");
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn eventually_passes() {
+        let attempts = Cell::new(0);
+        check(vec![
+            eventually!(Duration::from_secs(2), Duration::from_millis(1), {
+                attempts.set(attempts.get() + 1);
+                expect!({ attempts.get() } == 3)
+            }),
+            expect_eq!(attempts.get(), 3),
+        ]);
+    }
+
+    #[test]
+    fn eventually_times_out() {
+        let attempts = Cell::new(0);
+        if let Err(msg) = eventually!(Duration::from_millis(50), Duration::from_millis(10), {
+            attempts.set(attempts.get() + 1);
+            expect!({ attempts.get() } > 100)
+        }) {
+            let msg = trim_lines(&msg);
+            let attempts = attempts.get();
+            let expected_start = format!("\
+* Condition failed: {{ attempts.get() }} > 100
+                    ------------------
+                             |
+                             {}
+* Gave up after {} attempts in ", attempts, attempts);
+            check(vec![
+                expect!(attempts > 1),
+                expect!({ msg.starts_with(&expected_start) } == true),
+                expect!({ msg.trim_end().ends_with("(timeout: 50ms)") } == true),
+            ]);
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn spy_records_calls() {
+        let spy = Spy::new(|(a, b): (i32, i32)| a + b);
+        let results = [spy.call((1, 2)), spy.call((3, 4))];
+
+        check(vec![
+            expect!({ results == [3, 7] } == true),
+            expect!({ spy.calls() == vec![(1, 2), (3, 4)] } == true),
+            spy.called_times(2),
+            spy.called_with(&(3, 4)),
+        ]);
+    }
+
+    #[test]
+    fn spy_errors_list_recorded_calls() {
+        let spy = Spy::new(|name: &'static str| name.len());
+        spy.call("hello");
+        spy.call("bye");
+
+        assert_eq!(spy.called_times(1), Err("\
+* Spy expected to be called 1 time, but was called 2 times.
+  Recorded calls:
+    call[0]: \"hello\"
+    call[1]: \"bye\"\n".to_owned()));
+
+        assert_eq!(spy.called_with(&"hi"), Err("\
+* Spy expected to be called with \"hi\", but was not.
+  Recorded calls:
+    call[0]: \"hello\"
+    call[1]: \"bye\"\n".to_owned()));
+
+        assert!(spy.never_called().is_err());
+
+        let unused_spy = Spy::new(|_: ()| ());
+
+        assert_eq!(unused_spy.never_called(), Ok(()));
+        assert_eq!(unused_spy.called_with(&()), Err("\
+* Spy expected to be called with (), but was not.
+  No calls were recorded.\n".to_owned()));
+    }
+
+    #[test]
+    fn mock_records_calls_and_returns_configured_values() {
+        let mut repository = MockRepository::new();
+        repository.find.returns(Some("first".to_owned()));
+
+        let found = repository.find(1).unwrap();
+        repository.save(2, "second".to_owned());
+
+        repository.find.will(|(id,)| if id == 2 { Some("second".to_owned()) } else { None });
+
+        check(vec![
+            expect!({ found.as_str() } == "first"),
+            expect!({ repository.find(2).unwrap() } == "second"),
+            expect!({ repository.find(3).is_none() } == true),
+            repository.find.called_times(3),
+            repository.save.called_with(&(2, "second".to_owned())),
+            repository.clear.never_called(),
+        ]);
+    }
+
+    #[test]
+    fn mock_errors_name_the_method() {
+        let mut repository = MockRepository::new();
+        repository.clear();
+
+        assert_eq!(repository.clear.never_called(), Err("\
+* MockRepository::clear expected to never be called, but was called 1 time.
+  Recorded calls:
+    call[0]: ()\n".to_owned()));
+    }
+
+    #[test]
+    #[should_panic(expected = "No return value configured for MockRepository::find")]
+    fn mock_panics_if_return_value_is_not_configured() {
+        MockRepository::new().find(1);
+    }
+
+    fn check_failure(expects: Vec<Result<(), String>>) -> String {
+        let failure = panic::catch_unwind(|| check(expects)).unwrap_err();
+        failure.downcast_ref::<String>().cloned().unwrap_or_default()
+    }
+
+    /// A report without the elapsed time of its summary, which varies from run to run.
+    fn without_elapsed(report: &str) -> String {
+        let start = report.rfind(", in ").expect("no elapsed time in report");
+        let end = report[start + 5..].find(' ').map_or(report.len(), |end| start + 5 + end);
+        format!("{}{}", &report[..start], &report[end..])
+    }
+
+    #[test]
+    fn check_ends_with_summary() {
+        let report = check_failure(vec![Ok(()), Err("* Failed\n".to_owned()), Ok(())]);
+        let (report, elapsed) = report.split_at(report.rfind(" in ").unwrap());
+        assert_eq!(report, "\niteration[1]:\n* Failed\n\nSummary: 3 expectations, 2 passed, 1 failed,");
+        assert!(elapsed.ends_with('s'), "{}", elapsed);
+
+        set_check_options(CheckOptions { verbose: true, ..CheckOptions::default() });
+        let report = check_failure(vec![Ok(()), Err("* Failed\n".to_owned())]);
+        set_check_options(CheckOptions::default());
+        assert!(report.starts_with("\niteration[0]: passed\niteration[1]:\n* Failed\n\nSummary: 2 expectations"),
+                "{}", report);
+    }
+
+    #[test]
+    fn check_limits_failures() {
+        let failures = || (0..10).map(|index| if index % 2 == 0 { Ok(()) } else { Err(format!("* Failed {}\n", index)) });
+
+        set_check_options(CheckOptions { max_failures: Some(2), ..CheckOptions::default() });
+        let report = without_elapsed(&check_failure(failures().collect()));
+        assert_eq!(report, "
+iteration[1]:
+* Failed 1
+
+iteration[3]:
+* Failed 3
+
+... and 3 more failures omitted
+
+Summary: 10 expectations, 5 passed, 5 failed");
+
+        let evaluated = Cell::new(0);
+        set_check_options(CheckOptions { fail_fast: true, ..CheckOptions::default() });
+        let failure = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            check(failures().inspect(|_| evaluated.set(evaluated.get() + 1)))
+        }));
+        set_check_options(CheckOptions::default());
+        let report = failure.unwrap_err().downcast_ref::<String>().cloned().unwrap();
+        assert_eq!(without_elapsed(&report),
+                   "\niteration[1]:\n* Failed 1\n\nSummary: 2 expectations, 1 passed, 1 failed (stopped after 1 failure)");
+        assert_eq!(evaluated.get(), 2);
+
+        set_check_options(CheckOptions { max_failures: Some(0), fail_fast: true, ..CheckOptions::default() });
+        let report = without_elapsed(&check_failure(failures().collect()));
+        set_check_options(CheckOptions::default());
+        assert_eq!(report, "
+... and 1 more failure omitted
+
+Summary: 2 expectations, 1 passed, 1 failed (stopped after 1 failure)");
+    }
+
+    #[test]
+    fn check_groups_identical_failures() {
+        let report = without_elapsed(&check_failure((0..45).map(|index| match index {
+            3 | 7 | 12..=40 => expect!({ index < 41 } != true),
+            5 | 6 => Err("* Failed\n".to_owned()),
+            _ => Ok(())
+        }).collect()));
+        assert_eq_multiline("
+iterations [3, 7, 12..40]:
+* Condition failed: { index < 41 } != true
+                    --------------
+                           |
+                           true
+
+iterations [5, 6]:
+* Failed
+
+Summary: 45 expectations, 12 passed, 33 failed", &report);
+    }
+
+    #[test]
+    fn check_each_reports_panics_as_failures() {
+        let failure = panic::catch_unwind(|| {
+            check_each(vec!["1", "x", "3", "4"], |example| {
+                let number = example.parse::<u32>().unwrap();
+                expect!(number < 4)
+            })
+        }).unwrap_err();
+        let report = failure.downcast_ref::<String>().unwrap();
+        let (report, _) = report.split_at(report.rfind(" in ").unwrap());
+        assert_eq!(report, "
+iteration[1]:
+* Panicked: called `Result::unwrap()` on an `Err` value: ParseIntError { kind: InvalidDigit }
+
+iteration[3]:
+* Condition failed: number < 4
+                    |      |
+                    4      false
+
+Summary: 4 expectations, 2 passed, 2 failed,");
+    }
+
+    #[test]
+    fn check_parallel_reports_failures_in_order() {
+        set_diff_options(DiffOptions { context_lines: 7, ..DiffOptions::default() });
+        let failure = panic::catch_unwind(|| {
+            check_parallel(0..20, |example| {
+                if example == 5 {
+                    panic!("example {}", example);
+                }
+                if example % 6 == 0 {
+                    Err(format!("* Context lines of {}: {}\n", example, diff_options().context_lines))
+                } else {
+                    Ok(())
+                }
+            })
+        }).unwrap_err();
+        set_diff_options(DiffOptions::default());
+
+        let report = failure.downcast_ref::<String>().unwrap();
+        let (report, _) = report.split_at(report.rfind(" in ").unwrap());
+        assert_eq!(report, "
+iteration[0]:
+* Context lines of 0: 7
+
+iteration[5]:
+* Panicked: example 5
+
+iteration[6]:
+* Context lines of 6: 7
+
+iteration[12]:
+* Context lines of 12: 7
+
+iteration[18]:
+* Context lines of 18: 7
+
+Summary: 20 expectations, 15 passed, 5 failed,");
+    }
+
+    #[test]
+    fn with_fixture_tears_down_after_expectations() {
+        with_fixture(|fixture: &mut CountingFixture| {
+            fixture.0 += 1;
+            vec![
+                expect!({ fixture.0 } == 1),
+                expect!({ TEARDOWNS.with(|t| t.get()) } == 0),
+            ]
+        });
+
+        assert_eq!(TEARDOWNS.with(|t| t.get()), 1);
+    }
+
+    #[test]
+    fn with_fixture_tears_down_on_failure() {
+        let failure = panic::catch_unwind(|| {
+            with_fixture(|_: &mut CountingFixture| vec![expect!(1 > 2)]);
+        });
+        let test_panic = panic::catch_unwind(|| {
+            with_fixture(|_: &mut CountingFixture| -> Vec<Result<(), String>> {
+                panic!("test panicked")
+            });
+        });
+
+        check(vec![
+            expect!({ failure.is_err() } == true),
+            expect!({ test_panic.is_err() } == true),
+            expect!({ TEARDOWNS.with(|t| t.get()) } == 2),
+        ]);
+    }
+
+    #[test]
+    fn temp_dir_is_removed_on_drop() {
+        let dir = TempDir::new();
+        let path = dir.path().to_path_buf();
+        let existed = path.is_dir();
+        drop(dir);
+
+        check(vec![
+            expect!(existed),
+            expect!({ path.exists() } == false),
+        ]);
+    }
+
+    #[test]
+    fn file_expectations_errors() {
+        let dir = TempDir::new();
+        let file = dir.path().join("file.txt");
+        fs::write(&file, "Hello\nworld").unwrap();
+        let missing_file = dir.path().join("missing.txt");
+
+        assert_eq!(expect_file_exists!(missing_file), Err(format!("\
+* File does not exist: missing_file
+  Path: {}\n", missing_file.display())));
+
+        assert_eq!(expect_file_eq!(file, "Hello\nWorld"), Err(format!("\
+* File contents differ: file
+  Path: {}
+----- Difference -----
+ Hello
+{}{}{}
+{}{}{}
+----------------------\n", file.display(), Red.paint("-"), White.on(Red).paint("w"), Red.paint("orld"),
+                                              Green.paint("+"), White.on(Green).paint("W"), Green.paint("orld"))));
+
+        assert_eq!(expect_dir_contains!(dir.path(), ["file.txt", "other.txt"]), Err(format!("\
+* Directory is missing entries: dir.path()
+  Path: {}
+  Missing: [\"other.txt\"]
+  Contents: [\"file.txt\"]\n", dir.path().display())));
+
+        assert_eq!(expect_dir_contains!(dir.path(), vec!["other.txt"], "after {} writes", 1), Err(format!("\
+* Directory is missing entries: dir.path()
+  Path: {}
+  Missing: [\"other.txt\"]
+  Contents: [\"file.txt\"]
+  after 1 writes\n", dir.path().display())));
+    }
+
+    #[test]
+    fn expect_bytes_eq_shows_hex_dump() {
+        let actual = b"Hello world".to_vec();
+        let expected = b"Hello World!";
+        assert_eq!(expect_bytes_eq!(actual, expected), Err(format!("\
+* Bytes differ: actual and expected
+  First difference at offset 6 (0x6)
+  Lengths: 11 and 12
+----- Difference -----
+{}{}{}{}{}
+{}{}{}{}{}{}{}{}{}
+----------------------\n",
+            Red.paint("-00000000  48 65 6c 6c 6f 20 "), White.on(Red).paint("77"),
+            Red.paint(" 6f  72 6c 64                 |Hello "), White.on(Red).paint("w"),
+            Red.paint("orld|"),
+            Green.paint("+00000000  48 65 6c 6c 6f 20 "), White.on(Green).paint("57"),
+            Green.paint(" 6f  72 6c 64 "), White.on(Green).paint("21"),
+            Green.paint("              |Hello "), White.on(Green).paint("W"),
+            Green.paint("orld"), White.on(Green).paint("!"), Green.paint("|"))));
+
+        check(vec![expect_bytes_eq!(vec![0u8, 1, 2], [0u8, 1, 2])]);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn expect_json_eq_reports_paths_of_differences() {
+        let actual = r#"{"name": "Joe", "items": [{"price": 10}, {"price": 5}], "my id": 1, "extra": true}"#;
+        let expected = r#"{"items": [{"price": 12}, {"price": 5}, {"price": 7}], "name": "Joe", "my id": 2}"#;
+        assert_eq!(expect_json_eq!(actual, expected), Err("\
+* JSON values differ: actual and expected
+  $.extra: unexpected field with value true
+  $.items[0].price: 10 != 12
+  $.items[2]: missing element {\"price\":7}
+  $[\"my id\"]: 1 != 2
+".to_owned()));
+
+        check(vec![
+            expect_json_eq!(r#"{"a": [1, 2], "b": null}"#, r#"{ "b": null, "a": [1, 2] }"#),
+            expect_json_eq!(r#"{"a": 1, "b": 2}"#, ::serde_json::json!({"a": 1}), ignore_extra_fields),
+            expect_json_eq!(r#"{"a": 1, "b": [2.5]}"#, r#"{"a": 1.0, "b": [2.5]}"#),
+        ]);
+        assert_eq!(expect_json_eq!("1.5", "1"), Err("\
+* JSON values differ: \"1.5\" and \"1\"
+  $: 1.5 != 1
+".to_owned()));
+    }
+
+    #[test]
+    fn expect_seq_eq_matches_elements_of_large_sequences() {
+        let actual = (0..3000).collect::<Vec<_>>();
+        let mut expected = actual.clone();
+        expected[0] = -1;
+        expected[1500] = -2;
+        expected[2999] = -3;
+
+        let msg = expect_seq_eq!(actual, expected).unwrap_err();
+
+        let changed = msg.lines().filter(|line| line.starts_with('~')).collect::<Vec<_>>();
+        assert_eq!(changed, vec!["~ [0]", "~ [1500]", "~ [2999]"]);
+    }
+
+    #[test]
+    fn expect_map_eq_lists_different_keys() {
+        let actual = vec![("a", 1), ("b", 2), ("c", 3)].into_iter().collect::<HashMap<_, _>>();
+        let expected = vec![("a", 1), ("b", 4), ("d", 5)].into_iter().collect::<BTreeMap<_, _>>();
+        assert_eq!(expect_map_eq!(actual, expected), Err(format!("\
+* Maps differ: actual and expected
+  Only in actual:
+    \"c\" => 3
+  Only in expected:
+    \"d\" => 5
+  Different values:
+    \"b\"
+----- Difference -----
+{}{}
+{}{}
+----------------------
+", Red.paint("-"), White.on(Red).paint("2"), Green.paint("+"), White.on(Green).paint("4"))));
+
+        let actual = vec![3, 1, 2].into_iter().collect::<HashSet<_>>();
+        let expected = vec![1, 2, 4].into_iter().collect::<BTreeSet<_>>();
+        assert_eq!(expect_set_eq!(actual, expected), Err("\
+* Sets differ: actual and expected
+  Only in actual:
+    3
+  Only in expected:
+    4
+".to_owned()));
+
+        check(vec![
+            expect_map_eq!(BTreeMap::<u8, u8>::new(), HashMap::<u8, u8>::new()),
+            expect_set_eq!(vec![1, 2].into_iter().collect::<HashSet<_>>(), vec![2, 1].into_iter().collect::<HashSet<_>>()),
+        ]);
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Item {
+        name: &'static str,
+        price: u32,
+    }
+
+    #[test]
+    fn expect_seq_eq_shows_changed_elements() {
+        let actual = vec![Item { name: "a", price: 1 }, Item { name: "b", price: 2 }, Item { name: "c", price: 3 }];
+        let expected = [Item { name: "a", price: 1 }, Item { name: "b", price: 5 }, Item { name: "c", price: 3 },
+                        Item { name: "d", price: 4 }];
+        assert_eq!(expect_seq_eq!(actual, expected), Err(format!("\
+* Sequences differ: actual and expected
+  Lengths: 3 and 4
+~ [1]
+----- Difference -----
+ Item {{
+     name: \"b\",
+{}{} {}
+{}{} {}
+ }}
+----------------------
+{}
+", Red.paint("-"), Red.paint("price:"), White.on(Red).paint("2,"),
+           Green.paint("+"), Green.paint("price:"), White.on(Green).paint("5,"),
+           Green.paint("+ [3] Item { name: \"d\", price: 4 }"))));
+
+        assert_eq!(expect_seq_eq!([1, 2, 3, 4], [1, 3, 4, 5]), Err(format!("\
+* Sequences differ: [1, 2, 3, 4] and [1, 3, 4, 5]
+{}
+{}
+", Red.paint("- [1] 2"), Green.paint("+ [3] 5"))));
+
+        check(vec![expect_seq_eq!(vec![1, 2], [1, 2][..])]);
+    }
+
+    #[cfg(unix)]
+    fn write_out(text: &str) {
+        write!(io::stdout(), "{}", text).unwrap();
+    }
+
+    #[cfg(unix)]
+    fn write_err(text: &str) {
+        write!(io::stderr(), "{}", text).unwrap();
+    }
+
+    // the test harness may write to stdout concurrently, so only stderr is checked exactly
+    #[cfg(unix)]
+    #[test]
+    fn capture_output_from_stdout_and_stderr() {
+        let output = capture_output(|| {
+            writeln!(io::stdout(), "Hello stdout").unwrap();
+            writeln!(io::stderr(), "Hello stderr").unwrap();
+        });
+
+        check(vec![
+            expect!({ output.stdout.contains("Hello stdout\n") } == true),
+            expect_eq!(output.stderr.as_str(), "Hello stderr\n"),
+            expect_stdout_contains!(write_out("abc"), "abc"),
+            expect_stderr_eq!(write_err("abc"), "abc"),
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn capture_output_errors() {
+        assert_eq!(expect_stderr_eq!(write_err("Hello\nworld"), "Hello\nWorld"), Err(format!("\
+* Unexpected stderr from: write_err(\"Hello\\nworld\")
+----- Difference -----
+ Hello
+{}{}{}
+{}{}{}
+----------------------\n", Red.paint("-"), White.on(Red).paint("w"), Red.paint("orld"),
+                                              Green.paint("+"), White.on(Green).paint("W"), Green.paint("orld"))));
+
+        assert_eq!(expect_stderr_contains!(write_err("Hello"), "Bye"), Err(format!("\
+* The stderr from: write_err(\"Hello\")
+  does not contain the expected text.
+----- Difference -----
+{}{}{}{}
+{}{}{}
+----------------------\n", Red.paint("-"), White.on(Red).paint("H"), Red.paint("e"), White.on(Red).paint("llo"),
+                                              Green.paint("+"), White.on(Green).paint("By"), Green.paint("e"))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cmd_expectations() {
+        let dir = TempDir::new();
+        let result = Cmd::new("sh")
+            .args(["-c", "cat; echo \" $GREETING from $(basename $PWD)\"; echo oops >&2"])
+            .env("GREETING", "hi")
+            .stdin("input")
+            .current_dir(dir.path())
+            .run();
+        let dir_name = dir.path().file_name().unwrap().to_string_lossy().into_owned();
+
+        check(vec![
+            result.success(),
+            result.stdout_eq(&format!("input hi from {}\n", dir_name)),
+            result.stdout_contains("hi from"),
+            result.stderr_eq("oops\n"),
+            result.stderr_contains("oops"),
+        ]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cmd_passes_non_unicode_arguments_unchanged() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let result = Cmd::new("sh")
+            .args(["-c", "[ \"$1\" = \"$(printf '\\377')\" ]", "sh"])
+            .arg(OsStr::from_bytes(b"\xff"))
+            .run();
+
+        check(vec![result.success()]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cmd_errors_show_command_line() {
+        let result = Cmd::new("sh").arg("-c").arg("echo 'it failed' >&2; exit 3").run();
+
+        assert_eq!(result.success(), Err("\
+* Command: sh -c 'echo '\\''it failed'\\'' >&2; exit 3'
+  Expected exit code 0, but was 3.
+  stderr:
+it failed\n".to_owned()));
+
+        assert_eq!(result.stderr_eq("it passed\n"), Err(format!("\
+* Command: sh -c 'echo '\\''it failed'\\'' >&2; exit 3'
+  Unexpected stderr.
+----- Difference -----
+{}{} {}
+{}{} {}
+----------------------\n", Red.paint("-"), Red.paint("it"), White.on(Red).paint("failed"),
+                                              Green.paint("+"), Green.paint("it"), White.on(Green).paint("passed"))));
+
+        let missing = Cmd::new("vinegar-missing-command").env("A", "b c").run();
+
+        assert!(missing.stderr_contains("x").unwrap_err().starts_with("\
+* Command: A='b c' vinegar-missing-command
+  Unable to run command: "));
+    }
+
+    #[test]
+    fn expect_diff_collapses_unchanged_lines() {
+        let text1 = numbered_lines(20, &[]);
+        let text2 = numbered_lines(20, &[(10, "line ten")]);
+
+        if let Err(msg) = expect!({ &text1 } == { &text2 }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+... 6 unchanged lines ...
+{}
+ line 7
+ line 8
+ line 9
+{}{} {}
+{}{} {}
+ line 11
+ line 12
+ line 13
+... 7 unchanged lines ...
+----------------------\n", Cyan.paint("@@ -7,7 +7,7 @@"),
+                                             Red.paint("-"), Red.paint("line"), White.on(Red).paint("10"),
+                                             Green.paint("+"), Green.paint("line"), White.on(Green).paint("ten")));
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_diff_hunks_with_custom_context() {
+        set_diff_options(DiffOptions { context_lines: 1, ..DiffOptions::default() });
+
+        let text1 = numbered_lines(10, &[(3, "old"), (8, "old")]);
+        let text2 = numbered_lines(10, &[(3, "new\nnewer"), (8, "")]);
+
+        if let Err(msg) = expect!({ &text1 } == { &text2 }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+... 1 unchanged line ...
+{}
+ line 2
+{}
+{}
+{}
+ line 4
+... 2 unchanged lines ...
+{}
+ line 7
+{}
+{}
+ line 9
+... 1 unchanged line ...
+----------------------\n", Cyan.paint("@@ -2,3 +2,4 @@"),
+                                             Red.paint("-old"), Green.paint("+new"), Green.paint("+newer"),
+                                             Cyan.paint("@@ -7,3 +8,3 @@"),
+                                             [Red.paint("-").to_string(), White.on(Red).paint("old").to_string()].join(""),
+                                             Green.paint("+")));
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_diff_with_empty_text() {
+        if let Err(msg) = expect!({ "" } == { "hello" }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+{}{}
+{}{}
+----------------------\n", Red.paint("-"), "",
+                                             Green.paint("+"), White.on(Green).paint("hello")));
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_diff_with_configured_inline_diff() {
+        set_diff_options(DiffOptions { inline_diff: InlineDiff::Words, ..DiffOptions::default() });
+
+        if let Err(msg) = expect!({ "hello" } == { "hevvo" }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+{}{}
+{}{}
+----------------------\n", Red.paint("-"), White.on(Red).paint("hello"),
+                                             Green.paint("+"), White.on(Green).paint("hevvo")));
+        } else {
+            panic!("Should have failed");
+        }
+
+        set_diff_options(DiffOptions { inline_diff: InlineDiff::Characters, ..DiffOptions::default() });
+
+        if let Err(msg) = expect!({ "let user_name = 1;" } == { "let usr_name = 1;" }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+{}{}{}{}
+{}{}
+----------------------\n", Red.paint("-"), Red.paint("let us"), White.on(Red).paint("e"), Red.paint("r_name = 1;"),
+                                             Green.paint("+"), Green.paint("let usr_name = 1;")));
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_shows_invisible_characters() {
+        if let Err(msg) = expect!({ "hello " } == { "hello\u{a0}" }) {
+            assert_eq_multiline(&format!("\
+* Condition failed: {{ \"hello \" }} == {{ \"hello\\u{{a0}}\" }}
+                    ------------    -----------------
+                          |                  |
+                          |                  hello\\u{{a0}}
+                          |
+                          hello·
+----- Difference -----
+{}{}{}
+{}{}{}
+----------------------\n", Red.paint("-"), Red.paint("hello"), White.on(Red).paint("·"),
+                                         Green.paint("+"), Green.paint("hello"), White.on(Green).paint("\\u{a0}")), &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        if let Err(msg) = expect!({ "a\tb\r\nc" } == { "a    b\nc" }) {
+            assert_eq_multiline(&format!("\
+* Condition failed: {{ \"a\\tb\\r\\nc\" }} == {{ \"a    b\\nc\" }}
+                    ---------------    ---------------
+                           |                  |
+                           |                  a····b
+                           |                  c
+                           |
+                           a→b␍
+                           c
+----- Difference -----
+{}{}{}{}{}
+{}{}{}{}
+ c
+----------------------\n", Red.paint("-"), Red.paint("a"), White.on(Red).paint("→"), Red.paint("b"),
+                                         White.on(Red).paint("␍"),
+                                         Green.paint("+"), Green.paint("a"), White.on(Green).paint("····"),
+                                         Green.paint("b")), &msg);
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_diff_side_by_side() {
+        set_diff_options(DiffOptions { layout: DiffLayout::SideBySide, width: Some(27), ..DiffOptions::default() });
+
+        if let Err(msg) = expect!({ "first\nold line\nlast\nleft only" } == { "first\nnew line\nlast" }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+first          first
+{}{}     | {}{}
+last           last
+{}    <
+----------------------\n", White.on(Red).paint("old"), Red.paint(" line"),
+                                             White.on(Green).paint("new"), Green.paint(" line"),
+                                             Red.paint("left only")));
+        } else {
+            panic!("Should have failed");
+        }
+
+        if let Err(msg) = expect!({ "a\nb" } == { "a\nb\na line wider than the column" }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+a              a
+b              b
+             > {}
+             > {}
+             > {}
+----------------------\n", Green.paint("a line wider"), Green.paint(" than the co"), Green.paint("lumn")));
+        } else {
+            panic!("Should have failed");
+        }
+
+        // wide characters take two columns each
+        if let Err(msg) = expect!({ "日本語のテキスト\nx" } == { "日本語のテキスト\ny" }) {
+            assert_eq!(difference_of(&msg), format!("\
+----- Difference -----
+日本語のテキ   日本語のテキ
+スト           スト
+{}            | {}
+----------------------\n", White.on(Red).paint("x"), White.on(Green).paint("y")));
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_elides_huge_values() {
+        set_diff_options(DiffOptions {
+            inline_diff: InlineDiff::Characters, max_line_length: 10, max_lines: 4, ..DiffOptions::default()
+        });
+
+        let text1 = format!("{}a{}", "x".repeat(30), "y".repeat(30));
+        let text2 = format!("{}b{}", "x".repeat(30), "y".repeat(30));
+        if let Err(msg) = expect!({ text1.as_str() } == { text2.as_str() }) {
+            assert_eq_multiline(&format!("\
+* Condition failed: {{ text1.as_str() }} == {{ text2.as_str() }}
+                    ------------------    ------------------
+                             |                      |
+                             |                      [... 25 characters omitted ...]xxxxxbyyyy[... 26 characters omitted ...]
+                             |
+                             [... 25 characters omitted ...]xxxxxayyyy[... 26 characters omitted ...]
+----- Difference -----
+{}{}{}{}
+{}{}{}{}
+----------------------\n", Red.paint("-"), Red.paint("[... 25 characters omitted ...]xxxxx"), White.on(Red).paint("a"),
+                                         Red.paint("yyyy[... 26 characters omitted ...]"),
+                                         Green.paint("+"), Green.paint("[... 25 characters omitted ...]xxxxx"),
+                                         White.on(Green).paint("b"), Green.paint("yyyy[... 26 characters omitted ...]")),
+                                &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        let text1 = numbered_lines(20, &[]);
+        let text2 = numbered_lines(20, &[(2, "changed"), (10, "changed"), (18, "changed")]);
+        if let Err(msg) = expect!({ text1.as_str() } == { text2.as_str() }) {
+            assert!(trim_lines(&msg).contains("\
+                             |                      line 1
+                             |                      changed
+                             |                      line 3
+                             |                      line 4
+                             |                      ... 16 lines omitted ...
+                             |
+                             line 1
+                             line 2
+                             line 3
+                             line 4
+                             ... 16 lines omitted ...
+"));
+            assert!(difference_of(&msg).ends_with("... 22 lines omitted ...\n----------------------\n"));
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_aligns_diagram_with_wide_characters() {
+        if let Err(msg) = expect!({ "héllo 世界" } == { "hello 👍🏽" }) {
+            assert_eq_multiline("\
+* Condition failed: { \"héllo 世界\" } == { \"hello 👍🏽\" }
+                    ----------------    --------------
+                            |                   |
+                            |                   hello 👍🏽
+                            |
+                            héllo 世界
+", &msg[..msg.find("----- Difference -----").unwrap()]);
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_shows_every_sub_expression() {
+        let items = vec![1, 2, 3];
+        let (offset, limit) = (2, 10);
+        if let Err(msg) = expect!(items.len() + offset > limit) {
+            assert_eq_multiline("\
+* Condition failed: items.len() + offset > limit
+                    |     |     | |      | |
+                    |     3     5 2      | 10
+                    [1, 2, 3]            false
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        let name = String::from("Alice");
+        if let Err(msg) = expect!(name.to_lowercase().as_str() == "bob") {
+            assert_eq_multiline("\
+* Condition failed: name.to_lowercase().as_str() == \"bob\"
+                    |    |              |        |
+                    |    \"alice\"        \"alice\"  false
+                    \"Alice\"
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_evaluates_condition_once() {
+        let v = vec![1, 2];
+        if let Err(msg) = expect!(v.into_iter().count() == 3) {
+            assert_eq_multiline("\
+* Condition failed: v.into_iter().count() == 3
+                    | |           |       |
+                    | |           2       false
+                    | IntoIter([1, 2])
+                    [1, 2]
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        let mut q = vec![1, 2, 3];
+        if let Err(msg) = expect!(q.pop() == Some(2)) {
+            assert_eq_multiline("\
+* Condition failed: q.pop() == Some(2)
+                    | |     |  |
+                    | |     |  Some(2)
+                    | |     false
+                    | Some(3)
+                    [1, 2, 3]
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+        assert_eq!(q, vec![1, 2]);
+    }
+
+    #[test]
+    fn expect_shows_sub_expressions_that_were_evaluated() {
+        struct Item { size: usize }
+        let maybe: Option<Item> = None;
+        if let Err(msg) = expect!(maybe.is_some() && maybe.unwrap().size > 2) {
+            assert_eq_multiline("\
+* Condition failed: maybe.is_some() && maybe.unwrap().size > 2
+                    |     |         |
+                    |     false     false
+                    <Option<Item>>
+  Clauses:
+    maybe.is_some()         => false
+    maybe.unwrap().size > 2 => not evaluated
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_shows_result_of_each_clause() {
+        let (x, y) = (5, 12);
+        if let Err(msg) = expect!(x > 0 && y < 10 && x + y == 17) {
+            assert_eq_multiline("\
+* Condition failed: x > 0 && y < 10 && x + y == 17
+                    | |   |  | |    |
+                    5 |   |  | |    false
+                      |   |  | false
+                      |   |  12
+                      |   false
+                      true
+  Clauses:
+    x > 0       => true
+    y < 10      => false
+    x + y == 17 => not evaluated
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        let name = "Joe";
+        if let Err(msg) = expect!(name.is_empty() || name.starts_with('A')) {
+            assert_eq_multiline("\
+* Condition failed: name.is_empty() || name.starts_with('A')
+                    |    |          |  |    |
+                    |    false      |  |    false
+                    \"Joe\"           |  \"Joe\"
+                                    false
+  Clauses:
+    name.is_empty()       => false
+    name.starts_with('A') => false
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+    }
+
+    #[test]
+    fn expect_evaluates_each_clause_once() {
+        let calls = Cell::new(0);
+        let count = || {
+            calls.set(calls.get() + 1);
+            calls.get()
+        };
+        if let Err(msg) = expect!(count() == 1 && count() == 3 && count() > 0) {
+            assert_eq_multiline("\
+* Condition failed: count() == 1 && count() == 3 && count() > 0
+                    |       |    |  |       |    |
+                    1       true |  2       |    false
+                                 false      false
+  Clauses:
+    count() == 1 => true
+    count() == 3 => false
+    count() > 0  => not evaluated
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+        assert_eq!(calls.get(), 2);
+    }
+
+    fn never_evaluated() -> i32 {
+        panic!("Should not be evaluated")
+    }
+
+    #[test]
+    fn expect_shows_custom_message() {
+        let user = "Joe";
+        if let Err(msg) = expect!({ 2 + 2 } > { 5 }, "adding for user {}", user) {
+            assert_eq_multiline("\
+* Condition failed: { 2 + 2 } > { 5 }
+                    ---------   -----
+                        |         |
+                        |         5
+                        |
+                        4
+  adding for user Joe
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        if let Err(msg) = expect!({ 2 + 2 } > 5, "adding for user {}", user) {
+            assert_eq_multiline("\
+* Condition failed: { 2 + 2 } > 5
+                    ---------
+                        |
+                        4
+  adding for user Joe
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        let items = vec![1, 2];
+        if let Err(msg) = expect!(items.len() > 2, "items were\n{:?}", items) {
+            assert_eq_multiline("\
+* Condition failed: items.len() > 2
+                    |     |     |
+                    |     2     false
+                    [1, 2]
+  items were
+  [1, 2]
+", &msg);
+        } else {
+            panic!("Should have failed");
+        }
+
+        check(vec![
+            expect!({ 2 + 2 } == { 4 }, "never shown {}", never_evaluated()),
+            expect_eq!(2 + 2, 4, "never shown"),
+        ]);
+    }
+}
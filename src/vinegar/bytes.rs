@@ -0,0 +1,140 @@
+use std::cmp;
+
+use ansi_term::Colour;
+use ansi_term::Colour::{Green, Red, White};
+
+use super::diff::{format_diff, DiffLine};
+
+const BYTES_PER_ROW: usize = 16;
+
+///
+/// Create an expectation that two byte sequences are equal.
+///
+/// Both values may be of any type implementing `AsRef<[u8]>`, such as `Vec<u8>`, `&[u8]` or
+/// byte string literals. If they differ, both are shown as aligned hex dumps, with the bytes
+/// which differ highlighted, and the offset of the first difference is reported.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::check;
+///
+/// let encoded = vec![0x48, 0x69, 0x00];
+/// check(vec![
+///     expect_bytes_eq!(encoded, b"Hi\0"),
+/// ]);
+/// # }
+/// ```
+///
+/// The macro can also be called through its path, with a custom message:
+///
+/// ```edition2018
+/// let encoded = vec![0x48, 0x69];
+/// vinegar::vinegar::check(vec![
+///     vinegar::expect_bytes_eq!(encoded, b"Hi", "encoding {:?}", "Hi"),
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! expect_bytes_eq {
+    ($actual:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_bytes_eq(&$actual, stringify!($actual), &$expected, stringify!($expected))
+    }};
+
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_bytes_eq!($actual, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+#[doc(hidden)]
+pub fn internal_expect_bytes_eq<A, E>(actual: &A, actual_expr: &str, expected: &E, expected_expr: &str)
+                                      -> Result<(), String>
+    where A: AsRef<[u8]> + ?Sized, E: AsRef<[u8]> + ?Sized {
+    let (actual, expected) = (actual.as_ref(), expected.as_ref());
+    if actual == expected {
+        return Ok(());
+    }
+
+    let offset = actual.iter().zip(expected.iter())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let lengths = if actual.len() == expected.len() {
+        String::new()
+    } else {
+        format!("  Lengths: {} and {}\n", actual.len(), expected.len())
+    };
+
+    Err(format!("* Bytes differ: {} and {}\n  First difference at offset {} ({:#x})\n{}{}",
+                actual_expr, expected_expr, offset, offset, lengths, hex_dump_diff(actual, expected)))
+}
+
+/// Show both byte sequences as hex dumps, with the rows which differ one under the other.
+fn hex_dump_diff(old: &[u8], new: &[u8]) -> String {
+    let rows = cmp::max(old.len(), new.len()).div_ceil(BYTES_PER_ROW);
+    let row_bytes = |bytes: &[u8], row: usize| {
+        let start = cmp::min(row * BYTES_PER_ROW, bytes.len());
+        let end = cmp::min(start + BYTES_PER_ROW, bytes.len());
+        bytes[start..end].to_vec()
+    };
+
+    let mut lines = Vec::with_capacity(rows);
+    for row in 0..rows {
+        let offset = row * BYTES_PER_ROW;
+        let (old_row, new_row) = (row_bytes(old, row), row_bytes(new, row));
+        if old_row == new_row {
+            lines.push(DiffLine::same(&dump_row(offset, &old_row, &new_row, None)));
+        } else {
+            if !old_row.is_empty() {
+                lines.push(DiffLine::removed(dump_row(offset, &old_row, &new_row, Some(Red))));
+            }
+            if !new_row.is_empty() {
+                lines.push(DiffLine::added(dump_row(offset, &new_row, &old_row, Some(Green))));
+            }
+        }
+    }
+
+    format_diff(&lines, false)
+}
+
+/// A row of a hex dump, in the format of `hexdump -C`. If a colour is given, the row is painted
+/// with it, and the bytes which differ from the other row are highlighted.
+fn dump_row(offset: usize, bytes: &[u8], other: &[u8], color: Option<Colour>) -> String {
+    let is_different = |index: usize| other.get(index) != Some(&bytes[index]);
+
+    // the row is split into segments, each either highlighted or not
+    let mut segments: Vec<(String, bool)> = Vec::new();
+    let mut push = |text: String, is_highlighted: bool| match segments.last_mut() {
+        Some(last) if last.1 == is_highlighted => last.0.push_str(&text),
+        _ => segments.push((text, is_highlighted))
+    };
+
+    let prefix = match color {
+        Some(Red) => "-",
+        Some(_) => "+",
+        None => ""
+    };
+    push(format!("{}{:08x}  ", prefix, offset), false);
+    for index in 0..BYTES_PER_ROW {
+        if index > 0 {
+            push(if index == BYTES_PER_ROW / 2 { "  " } else { " " }.to_owned(), false);
+        }
+        match bytes.get(index) {
+            Some(byte) => push(format!("{:02x}", byte), is_different(index)),
+            None => push("  ".to_owned(), false)
+        }
+    }
+    push("  |".to_owned(), false);
+    for (index, &byte) in bytes.iter().enumerate() {
+        let c = if byte == b' ' || byte.is_ascii_graphic() { byte as char } else { '.' };
+        push(c.to_string(), is_different(index));
+    }
+    push("|".to_owned(), false);
+
+    segments.into_iter().map(|(text, is_highlighted)| match color {
+        Some(color) if is_highlighted => White.on(color).paint(text).to_string(),
+        Some(color) => color.paint(text).to_string(),
+        None => text
+    }).collect()
+}
@@ -0,0 +1,251 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::Mutex;
+
+use libc;
+
+use super::files::TempDir;
+use super::get_diff;
+
+static CAPTURE_LOCK: Mutex<()> = Mutex::new(());
+
+/// The output written to stdout and stderr while running a closure with
+/// [`capture_output`][capture_output].
+///
+/// [capture_output]: fn.capture_output.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapturedOutput {
+    /// Everything written to stdout.
+    pub stdout: String,
+    /// Everything written to stderr.
+    pub stderr: String,
+}
+
+/// Run the given closure, capturing everything written to stdout and stderr while it runs.
+///
+/// The output is captured by redirecting the process' stdout and stderr file descriptors,
+/// so output written by child processes and by non-Rust code is also captured.
+///
+/// Notice that, because the file descriptors are shared by the whole process, output written
+/// by other threads during the capture is also captured. Also, while running tests without
+/// the `--nocapture` flag, the Rust test harness intercepts calls to `print!` and `println!`
+/// before they reach the file descriptors, so only output written directly to
+/// `std::io::stdout()` and `std::io::stderr()` can be captured.
+///
+/// # Panics
+///
+/// If the file descriptors cannot be redirected, or if the closure panics, in which case
+/// the file descriptors are restored before the panic is resumed.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use vinegar::vinegar::capture_output;
+///
+/// let output = capture_output(|| {
+///     writeln!(std::io::stdout(), "Hello").unwrap();
+///     writeln!(std::io::stderr(), "Oops").unwrap();
+/// });
+///
+/// assert_eq!(output.stdout, "Hello\n");
+/// assert_eq!(output.stderr, "Oops\n");
+/// ```
+pub fn capture_output<F>(function: F) -> CapturedOutput where F: FnOnce() {
+    // only one capture can be active at a time as the file descriptors are global
+    let _lock = CAPTURE_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+    let dir = TempDir::new();
+    let stdout_path = dir.path().join("stdout");
+    let stderr_path = dir.path().join("stderr");
+
+    let result = {
+        let stdout_redirect = Redirect::new(libc::STDOUT_FILENO, File::create(&stdout_path));
+        let stderr_redirect = Redirect::new(libc::STDERR_FILENO, File::create(&stderr_path));
+        let result = panic::catch_unwind(AssertUnwindSafe(function));
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        drop(stderr_redirect);
+        drop(stdout_redirect);
+        result
+    };
+
+    if let Err(cause) = result {
+        panic::resume_unwind(cause);
+    }
+
+    CapturedOutput {
+        stdout: read_captured(&stdout_path),
+        stderr: read_captured(&stderr_path),
+    }
+}
+
+fn read_captured(path: &Path) -> String {
+    let bytes = fs::read(path).unwrap_or_else(|err| panic!("Unable to read captured output: {}", err));
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Redirects a file descriptor to a file, restoring the original file descriptor when dropped.
+struct Redirect {
+    fd: libc::c_int,
+    original_fd: libc::c_int,
+}
+
+impl Redirect {
+    fn new(fd: libc::c_int, file: io::Result<File>) -> Redirect {
+        let file = file.unwrap_or_else(|err| panic!("Unable to create capture file: {}", err));
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        unsafe {
+            let original_fd = libc::dup(fd);
+            if original_fd < 0 || libc::dup2(file.as_raw_fd(), fd) < 0 {
+                panic!("Unable to redirect file descriptor {}: {}", fd, io::Error::last_os_error());
+            }
+            Redirect { fd, original_fd }
+        }
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        unsafe {
+            libc::dup2(self.original_fd, self.fd);
+            libc::close(self.original_fd);
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn internal_expect_stdout_eq(output: &CapturedOutput, expr: &str, expected: &str) -> Result<(), String> {
+    expect_output_eq("stdout", &output.stdout, expr, expected)
+}
+
+#[doc(hidden)]
+pub fn internal_expect_stderr_eq(output: &CapturedOutput, expr: &str, expected: &str) -> Result<(), String> {
+    expect_output_eq("stderr", &output.stderr, expr, expected)
+}
+
+#[doc(hidden)]
+pub fn internal_expect_stdout_contains(output: &CapturedOutput, expr: &str, expected: &str) -> Result<(), String> {
+    expect_output_contains("stdout", &output.stdout, expr, expected)
+}
+
+#[doc(hidden)]
+pub fn internal_expect_stderr_contains(output: &CapturedOutput, expr: &str, expected: &str) -> Result<(), String> {
+    expect_output_contains("stderr", &output.stderr, expr, expected)
+}
+
+fn expect_output_eq(stream: &str, actual: &str, expr: &str, expected: &str) -> Result<(), String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("* Unexpected {} from: {}\n{}", stream, expr, get_diff(actual, expected)))
+    }
+}
+
+fn expect_output_contains(stream: &str, actual: &str, expr: &str, expected: &str) -> Result<(), String> {
+    if actual.contains(expected) {
+        Ok(())
+    } else {
+        Err(format!("* The {} from: {}\n  does not contain the expected text.\n{}",
+                    stream, expr, get_diff(actual, expected)))
+    }
+}
+
+///
+/// Create an expectation that running the given code writes exactly the expected text to stdout.
+///
+/// The output is captured with [`capture_output`][capture_output], so the same limitations apply.
+/// If the output differs, the difference between the actual and expected output is shown.
+///
+/// [capture_output]: vinegar/fn.capture_output.html
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use std::io::Write;
+/// use vinegar::vinegar::check;
+///
+/// fn greet(name: &str) {
+///     writeln!(std::io::stdout(), "Hello {}", name).unwrap();
+/// }
+///
+/// check(vec![
+///     expect_stdout_eq!(greet("Joe"), "Hello Joe\n"),
+/// ]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! expect_stdout_eq {
+    ($code:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_stdout_eq(
+            &$crate::vinegar::capture_output(|| { $code; }), stringify!($code), &$expected)
+    }};
+
+    ($code:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_stdout_eq!($code, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that running the given code writes exactly the expected text to stderr.
+///
+/// See [`expect_stdout_eq`][expect_stdout_eq].
+///
+/// [expect_stdout_eq]: macro.expect_stdout_eq.html
+#[macro_export]
+macro_rules! expect_stderr_eq {
+    ($code:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_stderr_eq(
+            &$crate::vinegar::capture_output(|| { $code; }), stringify!($code), &$expected)
+    }};
+
+    ($code:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_stderr_eq!($code, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that running the given code writes the expected text to stdout.
+///
+/// See [`expect_stdout_eq`][expect_stdout_eq].
+///
+/// [expect_stdout_eq]: macro.expect_stdout_eq.html
+#[macro_export]
+macro_rules! expect_stdout_contains {
+    ($code:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_stdout_contains(
+            &$crate::vinegar::capture_output(|| { $code; }), stringify!($code), &$expected)
+    }};
+
+    ($code:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_stdout_contains!($code, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that running the given code writes the expected text to stderr.
+///
+/// See [`expect_stdout_eq`][expect_stdout_eq].
+///
+/// [expect_stdout_eq]: macro.expect_stdout_eq.html
+#[macro_export]
+macro_rules! expect_stderr_contains {
+    ($code:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_stderr_contains(
+            &$crate::vinegar::capture_output(|| { $code; }), stringify!($code), &$expected)
+    }};
+
+    ($code:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_stderr_contains!($code, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
@@ -0,0 +1,305 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{diff_options, set_diff_options};
+
+/// The environment variable enabling the verbose mode of `check` when set to any value other
+/// than `0` or `false`.
+const VERBOSE_VARIABLE: &str = "VINEGAR_VERBOSE";
+
+/// Options controlling how [`check`][check] reports the results of expectations.
+///
+/// The options are set per thread with [`set_check_options`][set_check_options].
+///
+/// [check]: fn.check.html
+/// [set_check_options]: fn.set_check_options.html
+///
+/// # Examples
+///
+/// ```
+/// use vinegar::vinegar::{set_check_options, CheckOptions};
+///
+/// set_check_options(CheckOptions { verbose: true, ..CheckOptions::default() });
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheckOptions {
+    /// Whether passing expectations are also listed, in which case the report is printed to
+    /// stderr even if all expectations pass (default: `false`).
+    ///
+    /// The verbose mode can also be enabled by setting the `VINEGAR_VERBOSE` environment
+    /// variable to any value other than `0` or `false`.
+    pub verbose: bool,
+
+    /// The maximum number of failures shown in the report (default: `None`, i.e. all of them).
+    ///
+    /// Identical failures are shown only once, so they only count as one. Further failures are
+    /// only counted, and the report notes how many were omitted.
+    pub max_failures: Option<usize>,
+
+    /// Whether to stop checking expectations after the first failure or, if `max_failures` is
+    /// set to more than one, after that many failures (default: `false`).
+    ///
+    /// The remaining expectations are not evaluated, except by `check_parallel`, which evaluates
+    /// all of them before checking them in order.
+    pub fail_fast: bool,
+}
+
+thread_local! {
+    static CHECK_OPTIONS: RefCell<CheckOptions> = RefCell::new(CheckOptions::default());
+}
+
+/// Set the options used by `check` in the current thread.
+pub fn set_check_options(options: CheckOptions) {
+    CHECK_OPTIONS.with(|current| *current.borrow_mut() = options);
+}
+
+/// The options used by `check` in the current thread.
+pub fn check_options() -> CheckOptions {
+    CHECK_OPTIONS.with(|current| current.borrow().clone())
+}
+
+/// Check whether the given expectations have been met successfully.
+///
+/// The report ends with a summary line giving the number of expectations which were checked,
+/// passed and failed, and the time taken to evaluate them, including the time taken by lazy
+/// iterators to produce them. In verbose mode (see
+/// [`CheckOptions`][options]), passing expectations are also listed. The number of failures
+/// shown can be limited, and checking can stop at the first failures, with the same options.
+///
+/// Identical failures are shown once, with the indexes of all of them, e.g.
+/// `iterations [3, 7, 12..40]`, where ranges include both ends.
+///
+/// [options]: struct.CheckOptions.html
+///
+/// # Panics
+///
+/// If any expectation fails, this function panics with an error message showing why each
+/// expectation failed.
+pub fn check<I>(expects: I)
+    where I: IntoIterator<Item=Result<(), String>> {
+    check_since(expects, Instant::now());
+}
+
+/// Check the given expectations, reporting the time elapsed since they started to be evaluated.
+fn check_since<I>(expects: I, start: Instant)
+    where I: IntoIterator<Item=Result<(), String>> {
+    let options = check_options();
+    let verbose = options.verbose || is_verbose_from_env();
+    let shown_failures = options.max_failures.unwrap_or(usize::MAX);
+    let stop_after = if options.fail_fast { options.max_failures.unwrap_or(1).max(1) } else { usize::MAX };
+    let mut entries = Vec::new();
+    // identical failures are grouped, and shown once with the indexes of all of them
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut group_indexes: HashMap<String, usize> = HashMap::new();
+    let (mut total, mut failed, mut omitted) = (0, 0, 0);
+    let mut stopped = false;
+
+    for (index, expect) in expects.into_iter().enumerate() {
+        total += 1;
+        match expect {
+            Ok(()) => if verbose {
+                entries.push(ReportEntry::Passed(index));
+            },
+            Err(err) => {
+                failed += 1;
+                if let Some(&group) = group_indexes.get(&err) {
+                    groups[group].1.push(index);
+                } else if groups.len() < shown_failures {
+                    group_indexes.insert(err.clone(), groups.len());
+                    entries.push(ReportEntry::Failure(groups.len()));
+                    groups.push((err, vec![index]));
+                } else {
+                    // failure messages which will not be shown are dropped right away
+                    omitted += 1;
+                }
+                if failed >= stop_after {
+                    stopped = true;
+                    break;
+                }
+            }
+        }
+    }
+
+    let mut report = entries.into_iter().map(|entry| match entry {
+        ReportEntry::Passed(index) => format!("iteration[{}]: passed", index),
+        ReportEntry::Failure(group) => {
+            let (ref err, ref indexes) = groups[group];
+            if indexes.len() == 1 {
+                format!("iteration[{}]:\n{}", indexes[0], err)
+            } else {
+                format!("iterations [{}]:\n{}", index_ranges(indexes), err)
+            }
+        }
+    }).collect::<Vec<_>>();
+
+    if omitted > 0 {
+        report.push(format!("... and {} more failure{} omitted\n", omitted, if omitted == 1 { "" } else { "s" }));
+    }
+
+    let mut summary = summary(total, failed, start.elapsed());
+    if stopped {
+        summary.push_str(&format!(" (stopped after {} failure{})", failed, if failed == 1 { "" } else { "s" }));
+    }
+    if failed > 0 {
+        panic!("\n{}\n{}", report.join("\n"), summary);
+    } else if verbose {
+        eprintln!("{}\n{}", report.join("\n"), summary);
+    }
+}
+
+/// Check the expectation returned by the given closure for each example.
+///
+/// Unlike with [`check`][check], if the closure panics for an example, the panic is reported
+/// as the failure of that example, and the remaining examples are still checked.
+///
+/// [check]: fn.check.html
+///
+/// # Panics
+///
+/// If the expectation fails, or the closure panics, for any example, this function panics
+/// with an error message showing why each of them failed.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::check_each;
+///
+/// check_each(vec!["1", "22", "333"], |example| {
+///     let number = example.parse::<u32>().unwrap();
+///     expect_eq!(number.to_string().len(), example.len())
+/// });
+/// # }
+/// ```
+pub fn check_each<I, F>(examples: I, mut expectation: F)
+    where I: IntoIterator, F: FnMut(I::Item) -> Result<(), String> {
+    check(examples.into_iter().map(|example| run_example(&mut expectation, example)));
+}
+
+/// Check the expectation returned by the given closure for each example, like
+/// [`check_each`][check_each], but running the closure in parallel on several threads.
+///
+/// The failures are reported in the order of the examples, as by `check_each`. The diff options
+/// of the current thread (see [`set_diff_options`][set_diff_options]) are also used by the
+/// threads running the closure.
+///
+/// [check_each]: fn.check_each.html
+/// [set_diff_options]: fn.set_diff_options.html
+///
+/// # Panics
+///
+/// If the expectation fails, or the closure panics, for any example, this function panics
+/// with an error message showing why each of them failed.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::check_parallel;
+///
+/// check_parallel(1..100u64, |n| {
+///     let sum = (1..=n).sum::<u64>();
+///     expect_eq!(sum, n * (n + 1) / 2)
+/// });
+/// # }
+/// ```
+pub fn check_parallel<I, F>(examples: I, expectation: F)
+    where I: IntoIterator, I::Item: Send, F: Fn(I::Item) -> Result<(), String> + Sync {
+    let start = Instant::now();
+    let examples = examples.into_iter().enumerate().collect::<Vec<_>>();
+    let count = examples.len();
+    let threads = thread::available_parallelism().map(|threads| threads.get()).unwrap_or(1);
+    let options = diff_options();
+
+    // each thread takes the next example to run until there are none left
+    let examples = Mutex::new(examples.into_iter());
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.min(count) {
+            let (examples, expectation, options, sender) = (&examples, &expectation, &options, sender.clone());
+            scope.spawn(move || {
+                set_diff_options(options.clone());
+                loop {
+                    let next = examples.lock().unwrap_or_else(|err| err.into_inner()).next();
+                    match next {
+                        Some((index, example)) => {
+                            let _ = sender.send((index, run_example(expectation, example)));
+                        }
+                        None => break
+                    }
+                }
+            });
+        }
+    });
+    drop(sender);
+
+    let mut results = receiver.into_iter().collect::<Vec<_>>();
+    results.sort_by_key(|&(index, _)| index);
+    check_since(results.into_iter().map(|(_, result)| result), start);
+}
+
+/// Run the given closure for an example, turning a panic into a failure.
+fn run_example<T, F>(expectation: F, example: T) -> Result<(), String>
+    where F: FnOnce(T) -> Result<(), String> {
+    panic::catch_unwind(AssertUnwindSafe(|| expectation(example)))
+        .unwrap_or_else(|cause| Err(format!("* Panicked: {}\n", panic_message(&*cause))))
+}
+
+/// The message given to `panic!`, if it is a string.
+fn panic_message(cause: &(dyn Any + Send)) -> &str {
+    if let Some(message) = cause.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = cause.downcast_ref::<String>() {
+        message
+    } else {
+        "<unknown cause>"
+    }
+}
+
+enum ReportEntry {
+    Passed(usize),
+    /// The index of a group of identical failures.
+    Failure(usize),
+}
+
+/// List the given sorted indexes, abbreviating runs of at least three consecutive indexes as
+/// ranges including both ends, e.g. `3, 7, 12..40`.
+fn index_ranges(indexes: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &index in indexes {
+        match ranges.last_mut() {
+            Some(last) if last.1 + 1 == index => last.1 = index,
+            _ => ranges.push((index, index))
+        }
+    }
+    let mut result = Vec::with_capacity(ranges.len());
+    for (start, end) in ranges {
+        match end - start {
+            0 => result.push(start.to_string()),
+            1 => result.extend(vec![start.to_string(), end.to_string()]),
+            _ => result.push(format!("{}..{}", start, end))
+        }
+    }
+    result.join(", ")
+}
+
+fn is_verbose_from_env() -> bool {
+    match env::var(VERBOSE_VARIABLE) {
+        Ok(value) => value != "0" && value != "false",
+        Err(_) => false
+    }
+}
+
+fn summary(total: usize, failed: usize, elapsed: Duration) -> String {
+    format!("Summary: {} expectation{}, {} passed, {} failed, in {:?}",
+            total, if total == 1 { "" } else { "s" }, total - failed, failed, elapsed)
+}
@@ -0,0 +1,253 @@
+use std::ffi::{OsStr, OsString};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+use super::get_diff;
+
+/// A builder for a command to be executed, so that expectations about its exit status and
+/// output can be verified.
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(unix)] {
+/// use vinegar::vinegar::{check, Cmd};
+///
+/// let result = Cmd::new("sh")
+///     .args(["-c", "read name; echo Hello $name; echo Bye $name >&2"])
+///     .stdin("Joe")
+///     .run();
+///
+/// check(vec![
+///     result.success(),
+///     result.stdout_eq("Hello Joe\n"),
+///     result.stderr_contains("Bye"),
+/// ]);
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Cmd {
+    program: OsString,
+    args: Vec<OsString>,
+    envs: Vec<(OsString, OsString)>,
+    stdin: Option<String>,
+    current_dir: Option<PathBuf>,
+}
+
+impl Cmd {
+    /// Create a command to execute the given program, with no arguments.
+    pub fn new<S: AsRef<OsStr>>(program: S) -> Cmd {
+        Cmd {
+            program: program.as_ref().to_os_string(),
+            args: Vec::new(),
+            envs: Vec::new(),
+            stdin: None,
+            current_dir: None,
+        }
+    }
+
+    /// Add an argument to the command.
+    pub fn arg<S: AsRef<OsStr>>(mut self, arg: S) -> Cmd {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Add several arguments to the command.
+    pub fn args<I, S>(mut self, args: I) -> Cmd where I: IntoIterator<Item=S>, S: AsRef<OsStr> {
+        self.args.extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    /// Set an environment variable for the command.
+    pub fn env<K: AsRef<OsStr>, V: AsRef<OsStr>>(mut self, key: K, value: V) -> Cmd {
+        self.envs.push((key.as_ref().to_os_string(), value.as_ref().to_os_string()));
+        self
+    }
+
+    /// Set the text to be written to the command's stdin.
+    pub fn stdin<S: Into<String>>(mut self, input: S) -> Cmd {
+        self.stdin = Some(input.into());
+        self
+    }
+
+    /// Set the working directory of the command.
+    pub fn current_dir<P: AsRef<Path>>(mut self, dir: P) -> Cmd {
+        self.current_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// The command line that runs this command, including the environment variables it sets.
+    ///
+    /// Arguments which are not valid Unicode are shown with their invalid sequences replaced by
+    /// `U+FFFD`, but are passed unchanged to the command when it runs.
+    pub fn command_line(&self) -> String {
+        self.envs.iter()
+            .map(|(key, value)| format!("{}={}", key.to_string_lossy(), quote(value)))
+            .chain(Some(quote(&self.program)))
+            .chain(self.args.iter().map(|arg| quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Run the command, waiting for it to finish.
+    ///
+    /// If the command cannot be executed, all expectations on the returned result fail.
+    pub fn run(&self) -> CmdResult {
+        CmdResult {
+            command_line: self.command_line(),
+            current_dir: self.current_dir.clone(),
+            output: self.execute(),
+        }
+    }
+
+    fn execute(&self) -> Result<Output, String> {
+        let mut command = Command::new(&self.program);
+        command.args(&self.args)
+            .envs(self.envs.iter().map(|(key, value)| (key, value)))
+            .stdin(if self.stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if let Some(ref dir) = self.current_dir {
+            command.current_dir(dir);
+        }
+
+        let mut child = command.spawn().map_err(|err| err.to_string())?;
+
+        // write stdin from another thread, or the process may block writing its output
+        let writer = match (child.stdin.take(), self.stdin.clone()) {
+            (Some(mut child_stdin), Some(input)) => Some(thread::spawn(move || {
+                // the process may exit without reading all of its input
+                let _ = child_stdin.write_all(input.as_bytes());
+            })),
+            _ => None
+        };
+
+        let output = child.wait_with_output().map_err(|err| err.to_string());
+
+        if let Some(writer) = writer {
+            let _ = writer.join();
+        }
+
+        output
+    }
+}
+
+/// The result of running a [`Cmd`][cmd], providing expectations about its exit status and output.
+///
+/// Failures of these expectations always show the full command line that was run.
+///
+/// [cmd]: struct.Cmd.html
+#[derive(Debug)]
+pub struct CmdResult {
+    command_line: String,
+    current_dir: Option<PathBuf>,
+    output: Result<Output, String>,
+}
+
+impl CmdResult {
+    /// The exit code of the command, if it ran and was not terminated by a signal.
+    pub fn code(&self) -> Option<i32> {
+        self.output.as_ref().ok().and_then(|output| output.status.code())
+    }
+
+    /// The stdout of the command, if it ran.
+    pub fn stdout(&self) -> Option<String> {
+        self.output.as_ref().ok().map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    /// The stderr of the command, if it ran.
+    pub fn stderr(&self) -> Option<String> {
+        self.output.as_ref().ok().map(|output| String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+
+    /// Expect the command to have exited successfully.
+    pub fn success(&self) -> Result<(), String> {
+        self.exit_code(0)
+    }
+
+    /// Expect the command to have exited with the given exit code.
+    pub fn exit_code(&self, code: i32) -> Result<(), String> {
+        let output = self.output()?;
+        match output.status.code() {
+            Some(actual) if actual == code => Ok(()),
+            actual => {
+                let actual = actual.map(|c| c.to_string())
+                    .unwrap_or_else(|| "none (terminated by a signal)".to_owned());
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                let stderr = if stderr.is_empty() {
+                    String::new()
+                } else {
+                    format!("  stderr:\n{}\n", stderr.trim_end())
+                };
+                Err(format!("{}  Expected exit code {}, but was {}.\n{}",
+                            self.header(), code, actual, stderr))
+            }
+        }
+    }
+
+    /// Expect the command's stdout to be exactly the given text.
+    pub fn stdout_eq(&self, expected: &str) -> Result<(), String> {
+        self.stream_eq("stdout", self.stdout(), expected)
+    }
+
+    /// Expect the command's stderr to be exactly the given text.
+    pub fn stderr_eq(&self, expected: &str) -> Result<(), String> {
+        self.stream_eq("stderr", self.stderr(), expected)
+    }
+
+    /// Expect the command's stdout to contain the given text.
+    pub fn stdout_contains(&self, expected: &str) -> Result<(), String> {
+        self.stream_contains("stdout", self.stdout(), expected)
+    }
+
+    /// Expect the command's stderr to contain the given text.
+    pub fn stderr_contains(&self, expected: &str) -> Result<(), String> {
+        self.stream_contains("stderr", self.stderr(), expected)
+    }
+
+    fn stream_eq(&self, stream: &str, actual: Option<String>, expected: &str) -> Result<(), String> {
+        self.output()?;
+        let actual = actual.unwrap_or_default();
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(format!("{}  Unexpected {}.\n{}", self.header(), stream, get_diff(&actual, expected)))
+        }
+    }
+
+    fn stream_contains(&self, stream: &str, actual: Option<String>, expected: &str) -> Result<(), String> {
+        self.output()?;
+        let actual = actual.unwrap_or_default();
+        if actual.contains(expected) {
+            Ok(())
+        } else {
+            Err(format!("{}  The {} does not contain the expected text.\n{}",
+                        self.header(), stream, get_diff(&actual, expected)))
+        }
+    }
+
+    fn output(&self) -> Result<&Output, String> {
+        self.output.as_ref()
+            .map_err(|err| format!("{}  Unable to run command: {}\n", self.header(), err))
+    }
+
+    fn header(&self) -> String {
+        let dir = match self.current_dir {
+            Some(ref dir) => format!("  Directory: {}\n", dir.display()),
+            None => String::new()
+        };
+        format!("* Command: {}\n{}", self.command_line, dir)
+    }
+}
+
+fn quote(text: &OsStr) -> String {
+    let text = text.to_string_lossy();
+    if !text.is_empty() && text.chars().all(|c| c.is_alphanumeric() || "-_./=:,+@%".contains(c)) {
+        text.into_owned()
+    } else {
+        format!("'{}'", text.replace('\'', "'\\''"))
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Debug;
+use std::hash::{BuildHasher, Hash};
+
+use ansi_term::Colour::{Green, Red};
+
+use super::diff::{diff_sequences, Edit};
+use super::diff_options;
+use super::elide::{elide_line, omitted_lines};
+use super::get_diff;
+
+///
+/// Create an expectation that two maps have the same entries.
+///
+/// The maps may be `HashMap`s or `BTreeMap`s, not necessarily of the same type. If they differ,
+/// the keys only found in either map are listed, followed by the keys whose values differ, with
+/// the difference between each pair of values. Keys are sorted by their `Debug` representation,
+/// so the report is the same regardless of the iteration order of the maps.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use std::collections::{BTreeMap, HashMap};
+/// use vinegar::vinegar::check;
+///
+/// let mut ages = HashMap::new();
+/// ages.insert("Joe", 32);
+/// ages.insert("Mary", 28);
+///
+/// let mut expected = BTreeMap::new();
+/// expected.insert("Mary", 28);
+/// expected.insert("Joe", 32);
+///
+/// check(vec![
+///     expect_map_eq!(ages, expected),
+/// ]);
+/// # }
+/// ```
+///
+/// The macro can also be called through its path, with a custom message:
+///
+/// ```edition2018
+/// use std::collections::HashMap;
+///
+/// let ages = vec![("Joe", 32)].into_iter().collect::<HashMap<_, _>>();
+/// vinegar::vinegar::check(vec![
+///     vinegar::expect_map_eq!(ages, ages.clone(), "ages of {} users", ages.len()),
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! expect_map_eq {
+    ($actual:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_map_eq(&$actual, stringify!($actual), &$expected, stringify!($expected))
+    }};
+
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_map_eq!($actual, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that two sets have the same items.
+///
+/// The sets may be `HashSet`s or `BTreeSet`s, not necessarily of the same type. If they differ,
+/// the items only found in either set are listed, sorted by their `Debug` representation.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use std::collections::HashSet;
+/// use vinegar::vinegar::check;
+///
+/// let tags = vec!["rust", "test", "rust"].into_iter().collect::<HashSet<_>>();
+/// check(vec![
+///     expect_set_eq!(tags, vec!["test", "rust"].into_iter().collect::<HashSet<_>>()),
+/// ]);
+/// # }
+/// ```
+///
+/// The macro can also be called through its path, with a custom message:
+///
+/// ```edition2018
+/// use std::collections::BTreeSet;
+///
+/// let tags = vec!["rust"].into_iter().collect::<BTreeSet<_>>();
+/// vinegar::vinegar::check(vec![
+///     vinegar::expect_set_eq!(tags, tags.clone(), "{} tags", tags.len()),
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! expect_set_eq {
+    ($actual:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_set_eq(&$actual, stringify!($actual), &$expected, stringify!($expected))
+    }};
+
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_set_eq!($actual, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that two sequences have equal elements, in the same order.
+///
+/// The sequences may be of any type implementing `AsRef<[T]>`, such as `Vec<T>`, arrays or
+/// slices. If they differ, the elements are matched based on their longest common subsequence,
+/// and each element which was removed (`-`), added (`+`) or modified (`~`) is listed with its
+/// index, removed and modified elements with their index in the actual sequence and added
+/// elements with their index in the expected one. Modified elements are followed by the
+/// difference between their `Debug` representations.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::check;
+///
+/// let names = vec!["Joe", "Mary"];
+/// check(vec![
+///     expect_seq_eq!(names, ["Joe", "Mary"]),
+/// ]);
+/// # }
+/// ```
+///
+/// The macro can also be called through its path, with a custom message:
+///
+/// ```edition2018
+/// let names = vec!["Joe", "Mary"];
+/// vinegar::vinegar::check(vec![
+///     vinegar::expect_seq_eq!(names, ["Joe", "Mary"], "{} names", names.len()),
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! expect_seq_eq {
+    ($actual:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_seq_eq(&$actual, stringify!($actual), &$expected, stringify!($expected))
+    }};
+
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_seq_eq!($actual, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+/// A map which can be compared by `expect_map_eq`.
+#[doc(hidden)]
+pub trait InternalMap<K, V> {
+    fn internal_entries(&self) -> Vec<(&K, &V)>;
+    fn internal_get(&self, key: &K) -> Option<&V>;
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher> InternalMap<K, V> for HashMap<K, V, S> {
+    fn internal_entries(&self) -> Vec<(&K, &V)> {
+        self.iter().collect()
+    }
+
+    fn internal_get(&self, key: &K) -> Option<&V> {
+        self.get(key)
+    }
+}
+
+impl<K: Ord, V> InternalMap<K, V> for BTreeMap<K, V> {
+    fn internal_entries(&self) -> Vec<(&K, &V)> {
+        self.iter().collect()
+    }
+
+    fn internal_get(&self, key: &K) -> Option<&V> {
+        self.get(key)
+    }
+}
+
+/// A set which can be compared by `expect_set_eq`.
+#[doc(hidden)]
+pub trait InternalSet<T> {
+    fn internal_items(&self) -> Vec<&T>;
+    fn internal_contains(&self, item: &T) -> bool;
+}
+
+impl<T: Hash + Eq, S: BuildHasher> InternalSet<T> for HashSet<T, S> {
+    fn internal_items(&self) -> Vec<&T> {
+        self.iter().collect()
+    }
+
+    fn internal_contains(&self, item: &T) -> bool {
+        self.contains(item)
+    }
+}
+
+impl<T: Ord> InternalSet<T> for BTreeSet<T> {
+    fn internal_items(&self) -> Vec<&T> {
+        self.iter().collect()
+    }
+
+    fn internal_contains(&self, item: &T) -> bool {
+        self.contains(item)
+    }
+}
+
+#[doc(hidden)]
+pub fn internal_expect_map_eq<A, E, K, V>(actual: &A, actual_expr: &str, expected: &E, expected_expr: &str)
+                                          -> Result<(), String>
+    where A: InternalMap<K, V>, E: InternalMap<K, V>, K: Debug, V: Debug + PartialEq {
+    let only_in = |map: &dyn InternalMap<K, V>, other: &dyn InternalMap<K, V>| sorted(map.internal_entries().into_iter()
+        .filter(|&(key, _)| other.internal_get(key).is_none())
+        .map(|(key, value)| (format!("{:?}", key), format!("{:?}", value)))
+        .collect());
+    let only_in_actual = only_in(actual, expected);
+    let only_in_expected = only_in(expected, actual);
+
+    let changed = sorted(actual.internal_entries().into_iter()
+        .filter_map(|(key, value)| match expected.internal_get(key) {
+            Some(other) if other != value =>
+                Some((format!("{:?}", key), get_diff(&format!("{:#?}", value), &format!("{:#?}", other)))),
+            _ => None
+        })
+        .collect());
+
+    if only_in_actual.is_empty() && only_in_expected.is_empty() && changed.is_empty() {
+        return Ok(());
+    }
+
+    let entries = |entries: &[(String, String)]| entries.iter()
+        .map(|(key, value)| format!("    {} => {}\n", show(key), show(value)))
+        .collect::<String>();
+    let mut result = format!("* Maps differ: {} and {}\n", actual_expr, expected_expr);
+    if !only_in_actual.is_empty() {
+        result.push_str(&format!("  Only in {}:\n{}", actual_expr, entries(&only_in_actual)));
+    }
+    if !only_in_expected.is_empty() {
+        result.push_str(&format!("  Only in {}:\n{}", expected_expr, entries(&only_in_expected)));
+    }
+    if !changed.is_empty() {
+        result.push_str("  Different values:\n");
+        for (key, diff) in changed {
+            result.push_str(&format!("    {}\n{}", show(&key), diff));
+        }
+    }
+    Err(result)
+}
+
+#[doc(hidden)]
+pub fn internal_expect_set_eq<A, E, T>(actual: &A, actual_expr: &str, expected: &E, expected_expr: &str)
+                                       -> Result<(), String>
+    where A: InternalSet<T>, E: InternalSet<T>, T: Debug {
+    let only_in = |set: &dyn InternalSet<T>, other: &dyn InternalSet<T>| sorted(set.internal_items().into_iter()
+        .filter(|item| !other.internal_contains(item))
+        .map(|item| format!("{:?}", item))
+        .collect());
+    let only_in_actual = only_in(actual, expected);
+    let only_in_expected = only_in(expected, actual);
+
+    if only_in_actual.is_empty() && only_in_expected.is_empty() {
+        return Ok(());
+    }
+
+    let items = |items: &[String]| items.iter()
+        .map(|item| format!("    {}\n", show(item)))
+        .collect::<String>();
+    let mut result = format!("* Sets differ: {} and {}\n", actual_expr, expected_expr);
+    if !only_in_actual.is_empty() {
+        result.push_str(&format!("  Only in {}:\n{}", actual_expr, items(&only_in_actual)));
+    }
+    if !only_in_expected.is_empty() {
+        result.push_str(&format!("  Only in {}:\n{}", expected_expr, items(&only_in_expected)));
+    }
+    Err(result)
+}
+
+#[doc(hidden)]
+pub fn internal_expect_seq_eq<A, E, T>(actual: &A, actual_expr: &str, expected: &E, expected_expr: &str)
+                                       -> Result<(), String>
+    where A: AsRef<[T]> + ?Sized, E: AsRef<[T]> + ?Sized, T: Debug + PartialEq {
+    let (actual, expected) = (actual.as_ref(), expected.as_ref());
+    if actual == expected {
+        return Ok(());
+    }
+
+    let mut lines = Vec::new();
+    let edits = diff_sequences(actual, expected);
+    for change in edits.split(|edit| matches!(*edit, Edit::Same(..))).filter(|change| !change.is_empty()) {
+        let removed = change.iter().filter_map(|edit| match *edit { Edit::Rem(i) => Some(i), _ => None });
+        let added = change.iter().filter_map(|edit| match *edit { Edit::Add(j) => Some(j), _ => None });
+        let (removed, added) = (removed.collect::<Vec<_>>(), added.collect::<Vec<_>>());
+
+        // removed and added elements at the same position of a change are modified elements
+        for (&i, &j) in removed.iter().zip(added.iter()) {
+            let index = if i == j { format!("[{}]", i) } else { format!("[{}] (expected [{}])", i, j) };
+            lines.push(format!("~ {}\n{}", index, get_diff(&format!("{:#?}", actual[i]), &format!("{:#?}", expected[j]))));
+        }
+        for &i in removed.iter().skip(added.len()) {
+            lines.push(format!("{}\n", Red.paint(format!("- [{}] {}", i, show(&format!("{:?}", actual[i]))))));
+        }
+        for &j in added.iter().skip(removed.len()) {
+            lines.push(format!("{}\n", Green.paint(format!("+ [{}] {}", j, show(&format!("{:?}", expected[j]))))));
+        }
+    }
+
+    let max_lines = diff_options().max_lines;
+    if lines.len() > max_lines {
+        let omitted = lines.len() - max_lines;
+        lines.truncate(max_lines);
+        lines.push(format!("{}\n", omitted_lines(omitted)));
+    }
+
+    let lengths = if actual.len() == expected.len() {
+        String::new()
+    } else {
+        format!("  Lengths: {} and {}\n", actual.len(), expected.len())
+    };
+    Err(format!("* Sequences differ: {} and {}\n{}{}", actual_expr, expected_expr, lengths, lines.concat()))
+}
+
+/// Sort rendered entries, so that reports do not depend on iteration order.
+fn sorted<T: Ord>(mut entries: Vec<T>) -> Vec<T> {
+    entries.sort();
+    entries
+}
+
+fn show(text: &str) -> String {
+    elide_line(&text.replace('\n', " "), 0, diff_options().max_line_length)
+}
@@ -0,0 +1,530 @@
+use std::cell::RefCell;
+use std::cmp;
+use std::ops::Deref;
+
+use ansi_term::Colour;
+use ansi_term::Colour::{Cyan, Green, Red, White};
+use difference::{Changeset, Difference};
+
+use super::elide::{elide_line, first_difference, omitted_lines};
+use super::side_by_side::{side_by_side_lines, terminal_width};
+
+/// The maximum number of cells in the table used to compute the longest common subsequence
+/// of two sequences. Larger inputs are matched with a faster heuristic, rather than using
+/// an excessive amount of memory.
+const MAX_LCS_TABLE_SIZE: usize = 4_000_000;
+
+/// How far ahead, in each sequence, the heuristic used for large inputs looks for the next
+/// matching items.
+const MATCH_WINDOW: usize = 32;
+
+/// The maximum length, in characters, of lines shown with a character-by-character diff
+/// when using `InlineDiff::Auto`.
+const MAX_AUTO_CHARACTER_DIFF_LENGTH: usize = 100;
+
+/// Options controlling how the differences between values are shown in failure messages.
+///
+/// The options are set per thread with [`set_diff_options`][set_diff_options].
+///
+/// [set_diff_options]: fn.set_diff_options.html
+///
+/// # Examples
+///
+/// ```
+/// use vinegar::vinegar::{set_diff_options, DiffOptions};
+///
+/// set_diff_options(DiffOptions { context_lines: 1, ..DiffOptions::default() });
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOptions {
+    /// The number of unchanged lines shown before and after each changed line (default: 3).
+    ///
+    /// Longer runs of unchanged lines are collapsed, in which case the diff is split into hunks
+    /// with `@@ -a,b +c,d @@` headers giving the line numbers of each hunk.
+    pub context_lines: usize,
+
+    /// How the changes within a line are highlighted when it is replaced by another line
+    /// (default: `InlineDiff::Auto`).
+    pub inline_diff: InlineDiff,
+
+    /// How the lines of both texts are laid out (default: `DiffLayout::Unified`).
+    pub layout: DiffLayout,
+
+    /// The total width, in columns, of side-by-side diffs (default: `None`).
+    ///
+    /// If not set, the width of the terminal is used, as given by the `COLUMNS` environment
+    /// variable or, on Unix, by the terminal itself. If that is unknown, 120 columns are used.
+    pub width: Option<usize>,
+
+    /// The maximum number of characters shown for each line of a value or diff (default: 500).
+    ///
+    /// Longer lines are shortened around their first difference, noting how many characters
+    /// were omitted.
+    pub max_line_length: usize,
+
+    /// The maximum number of lines shown for each value or diff (default: 100).
+    ///
+    /// Longer values are shortened around their first difference, noting how many lines
+    /// were omitted.
+    pub max_lines: usize,
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            context_lines: 3,
+            inline_diff: InlineDiff::Auto,
+            layout: DiffLayout::Unified,
+            width: None,
+            max_line_length: 500,
+            max_lines: 100,
+        }
+    }
+}
+
+/// How the lines of two texts are laid out when showing their differences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffLayout {
+    /// Removed lines, prefixed with `-`, are shown above the added lines, prefixed with `+`.
+    Unified,
+    /// Lines of the two texts are shown in two columns, with matching lines side by side.
+    ///
+    /// The marker between the columns shows whether a line was changed (`|`), only exists on
+    /// the left (`<`) or only exists on the right (`>`). Lines too long to fit are wrapped.
+    SideBySide,
+}
+
+/// How the changes within a line are highlighted when a single line is replaced by another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InlineDiff {
+    /// Highlight characters if both lines are short and contain a single token
+    /// (i.e. no whitespace), otherwise highlight words.
+    Auto,
+    /// Highlight the words that differ.
+    Words,
+    /// Highlight the characters that differ.
+    Characters,
+}
+
+impl InlineDiff {
+    pub(crate) fn by_characters(self, x: &str, y: &str) -> bool {
+        let is_short_token = |line: &str| line.chars().count() <= MAX_AUTO_CHARACTER_DIFF_LENGTH
+            && !line.chars().any(char::is_whitespace);
+        match self {
+            InlineDiff::Auto => is_short_token(x) && is_short_token(y),
+            InlineDiff::Words => false,
+            InlineDiff::Characters => true,
+        }
+    }
+}
+
+thread_local! {
+    static DIFF_OPTIONS: RefCell<DiffOptions> = RefCell::new(DiffOptions::default());
+}
+
+/// Set the options used to show differences in failure messages created in the current thread.
+pub fn set_diff_options(options: DiffOptions) {
+    DIFF_OPTIONS.with(|current| *current.borrow_mut() = options);
+}
+
+/// The options used to show differences in failure messages created in the current thread.
+pub fn diff_options() -> DiffOptions {
+    DIFF_OPTIONS.with(|current| current.borrow().clone())
+}
+
+/// A single step of the edit script that turns one sequence into another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Edit {
+    /// The items at the given indexes of the old and new sequences are the same.
+    Same(usize, usize),
+    /// The item at the given index of the old sequence was removed.
+    Rem(usize),
+    /// The item at the given index of the new sequence was added.
+    Add(usize),
+}
+
+/// Compute the shortest edit script turning `old` into `new`, based on their longest common
+/// subsequence. Within each changed region, removals come before additions.
+pub(crate) fn diff_sequences<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new.iter())
+        .take_while(|&(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+        .take_while(|&(a, b)| a == b)
+        .count();
+
+    let mut edits = Vec::with_capacity(cmp::max(old.len(), new.len()));
+    edits.extend((0..prefix).map(|i| Edit::Same(i, i)));
+    lcs_edits(&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix], prefix, &mut edits);
+    edits.extend((0..suffix).map(|i| Edit::Same(old.len() - suffix + i, new.len() - suffix + i)));
+    edits
+}
+
+fn lcs_edits<T: PartialEq>(old: &[T], new: &[T], offset: usize, edits: &mut Vec<Edit>) {
+    let (n, m) = (old.len(), new.len());
+
+    if n.saturating_mul(m) > MAX_LCS_TABLE_SIZE {
+        return window_edits(old, new, offset, edits);
+    }
+
+    // lengths[i * width + j] is the length of the LCS of old[i..] and new[j..]
+    let width = m + 1;
+    let mut lengths = vec![0u32; (n + 1) * width];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i * width + j] = if old[i] == new[j] {
+                lengths[(i + 1) * width + j + 1] + 1
+            } else {
+                cmp::max(lengths[(i + 1) * width + j], lengths[i * width + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old[i] == new[j] {
+            edits.push(Edit::Same(offset + i, offset + j));
+            i += 1;
+            j += 1;
+        } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+            edits.push(Edit::Rem(offset + i));
+            i += 1;
+        } else {
+            edits.push(Edit::Add(offset + j));
+            j += 1;
+        }
+    }
+    edits.extend((i..n).map(|i| Edit::Rem(offset + i)));
+    edits.extend((j..m).map(|j| Edit::Add(offset + j)));
+}
+
+/// Compute an edit script in linear time, by repeatedly matching the nearest items which are
+/// equal within a window ahead of the current position in each sequence. The script is not
+/// necessarily the shortest, but changes far apart from each other are still found.
+fn window_edits<T: PartialEq>(old: &[T], new: &[T], offset: usize, edits: &mut Vec<Edit>) {
+    let (n, m) = (old.len(), new.len());
+    let (mut i, mut j) = (0, 0);
+    // the removals and additions of the current changed region, flushed before the next match
+    let (mut removed, mut added) = (Vec::new(), Vec::new());
+
+    while i < n && j < m {
+        let next_match = (0..2 * MATCH_WINDOW)
+            .flat_map(|distance| (0..=distance).map(move |a| (a, distance - a)))
+            .filter(|&(a, b)| a < MATCH_WINDOW && b < MATCH_WINDOW && i + a < n && j + b < m)
+            .find(|&(a, b)| old[i + a] == new[j + b]);
+        match next_match {
+            Some((a, b)) => {
+                removed.extend((i..i + a).map(|i| Edit::Rem(offset + i)));
+                added.extend((j..j + b).map(|j| Edit::Add(offset + j)));
+                edits.append(&mut removed);
+                edits.append(&mut added);
+                edits.push(Edit::Same(offset + i + a, offset + j + b));
+                i += a + 1;
+                j += b + 1;
+            }
+            None => {
+                // nothing matches within the window, which is skipped in both sequences
+                let (a, b) = (cmp::min(MATCH_WINDOW, n - i), cmp::min(MATCH_WINDOW, m - j));
+                removed.extend((i..i + a).map(|i| Edit::Rem(offset + i)));
+                added.extend((j..j + b).map(|j| Edit::Add(offset + j)));
+                i += a;
+                j += b;
+            }
+        }
+    }
+    removed.extend((i..n).map(|i| Edit::Rem(offset + i)));
+    added.extend((j..m).map(|j| Edit::Add(offset + j)));
+    edits.append(&mut removed);
+    edits.append(&mut added);
+}
+
+/// A rendered line of a diff, which may span several lines of text when wrapped.
+pub(crate) struct DiffLine {
+    pub(crate) text: String,
+    pub(crate) is_change: bool,
+    pub(crate) old_lines: usize,
+    pub(crate) new_lines: usize,
+}
+
+impl DiffLine {
+    pub(crate) fn same(line: &str) -> DiffLine {
+        DiffLine { text: line_diff(line, Option::None, ' '), is_change: false, old_lines: 1, new_lines: 1 }
+    }
+
+    pub(crate) fn removed(text: String) -> DiffLine {
+        DiffLine { text, is_change: true, old_lines: 1, new_lines: 0 }
+    }
+
+    pub(crate) fn added(text: String) -> DiffLine {
+        DiffLine { text, is_change: true, old_lines: 0, new_lines: 1 }
+    }
+}
+
+pub(crate) fn get_diff(text1: &str, text2: &str) -> String {
+    let options = diff_options();
+
+    // a final new-line on both sides is not worth showing
+    let (text1, text2) = if text1.ends_with('\n') && text2.ends_with('\n') {
+        (&text1[..text1.len() - 1], &text2[..text2.len() - 1])
+    } else {
+        (text1, text2)
+    };
+
+    let old = text1.split('\n').collect::<Vec<_>>();
+    let new = text2.split('\n').collect::<Vec<_>>();
+    let edits = diff_sequences(&old, &new);
+    let (old, new) = elide_long_lines(&old, &new, &edits, options.max_line_length);
+    let old = old.iter().map(String::as_str).collect::<Vec<_>>();
+    let new = new.iter().map(String::as_str).collect::<Vec<_>>();
+    let lines = match options.layout {
+        DiffLayout::Unified => diff_lines(&old, &new, &edits, options.inline_diff),
+        DiffLayout::SideBySide => side_by_side_lines(&old, &new, &edits, options.inline_diff,
+                                                     options.width.unwrap_or_else(terminal_width)),
+    };
+
+    format_diff(&lines, true)
+}
+
+/// Format the given diff lines, collapsing the unchanged lines far from any change and limiting
+/// the number of lines shown, with a header before each hunk if requested.
+pub(crate) fn format_diff(lines: &[DiffLine], show_hunk_headers: bool) -> String {
+    let options = diff_options();
+    let hunks = hunks(lines, options.context_lines);
+    let is_complete = hunks.len() == 1 && hunks[0] == (0, lines.len());
+    let mut rows = Vec::new();
+    let mut shown_until = 0;
+
+    for (start, end) in hunks {
+        if start > shown_until {
+            rows.push(unchanged_lines_marker(start - shown_until));
+        }
+        if show_hunk_headers && !is_complete {
+            rows.push(hunk_header(lines, start, end));
+        }
+        rows.extend(lines[start..end].iter().map(|line| line.text.clone()));
+        shown_until = end;
+    }
+
+    if lines.len() > shown_until {
+        rows.push(unchanged_lines_marker(lines.len() - shown_until));
+    }
+
+    if rows.len() > options.max_lines {
+        let omitted = rows.len() - options.max_lines;
+        rows.truncate(options.max_lines);
+        rows.push(omitted_lines(omitted));
+    }
+
+    let mut result = String::with_capacity(rows.iter().map(|row| row.len() + 1).sum::<usize>() + 64);
+    result.push_str("----- Difference -----\n");
+    for row in rows {
+        result.push_str(&row);
+        result.push('\n');
+    }
+    result.push_str("----------------------\n");
+
+    result
+}
+
+/// Shorten the lines longer than the given length. Lines replaced by a single other line are
+/// shortened around their first difference, so that it remains visible.
+fn elide_long_lines(old: &[&str], new: &[&str], edits: &[Edit], max_line_length: usize) -> (Vec<String>, Vec<String>) {
+    let mut old_lines = old.iter().map(|line| elide_line(line, 0, max_line_length)).collect::<Vec<_>>();
+    let mut new_lines = new.iter().map(|line| elide_line(line, 0, max_line_length)).collect::<Vec<_>>();
+
+    let changes = edits.split(|edit| matches!(*edit, Edit::Same(..)));
+    for change in changes.filter(|change| change.len() == 2) {
+        if let (Edit::Rem(i), Edit::Add(j)) = (change[0], change[1]) {
+            let (_, column) = first_difference(old[i], new[j]);
+            old_lines[i] = elide_line(old[i], column, max_line_length);
+            new_lines[j] = elide_line(new[j], column, max_line_length);
+        }
+    }
+
+    (old_lines, new_lines)
+}
+
+fn diff_lines(old: &[&str], new: &[&str], edits: &[Edit], inline_diff: InlineDiff) -> Vec<DiffLine> {
+    let mut lines = Vec::with_capacity(edits.len());
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+
+    for edit in edits {
+        match *edit {
+            Edit::Same(i, _) => {
+                push_changes(&mut lines, &mut removed, &mut added, inline_diff);
+                if has_control_characters(old[i]) {
+                    lines.push(DiffLine::same(&visible_whitespace(old[i])));
+                } else {
+                    lines.push(DiffLine::same(old[i]));
+                }
+            }
+            Edit::Rem(i) => removed.push(old[i]),
+            Edit::Add(j) => added.push(new[j])
+        }
+    }
+    push_changes(&mut lines, &mut removed, &mut added, inline_diff);
+
+    lines
+}
+
+fn push_changes(lines: &mut Vec<DiffLine>, removed: &mut Vec<&str>, added: &mut Vec<&str>,
+                inline_diff: InlineDiff) {
+    let show_invisible = needs_visible_whitespace(&removed.join("\n"), &added.join("\n"));
+    let visible = |line: &&str| if show_invisible { visible_whitespace(line) } else { line.to_string() };
+    let removed_lines = removed.iter().map(visible).collect::<Vec<_>>();
+    let added_lines = added.iter().map(visible).collect::<Vec<_>>();
+
+    if removed_lines.len() == 1 && added_lines.len() == 1 {
+        let (x, y) = (&removed_lines[0], &added_lines[0]);
+        // invisible characters are shown as single characters, which words would hide
+        if show_invisible || inline_diff.by_characters(x, y) {
+            lines.push(DiffLine::removed(char_by_char_diff(x, y, true)));
+            lines.push(DiffLine::added(char_by_char_diff(x, y, false)));
+        } else {
+            lines.push(DiffLine::removed(word_by_word_diff(x, y, true)));
+            lines.push(DiffLine::added(word_by_word_diff(x, y, false)));
+        }
+    } else {
+        // several lines changed, show them without word-by-word diff
+        lines.extend(removed_lines.iter().map(|line| DiffLine::removed(line_diff(line, Option::Some(Red), '-'))));
+        lines.extend(added_lines.iter().map(|line| DiffLine::added(line_diff(line, Option::Some(Green), '+'))));
+    }
+    removed.clear();
+    added.clear();
+}
+
+/// Whether invisible characters should be made visible when showing the given texts, which is
+/// the case if they differ only in whitespace, or if any of them contains control characters.
+pub(crate) fn needs_visible_whitespace(x: &str, y: &str) -> bool {
+    let non_whitespace = |text: &str| text.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    has_control_characters(x) || has_control_characters(y) || (x != y && non_whitespace(x) == non_whitespace(y))
+}
+
+pub(crate) fn has_control_characters(text: &str) -> bool {
+    text.chars().any(|c| c.is_control() && c != '\n' && c != '\t')
+}
+
+/// Replace whitespace (other than new-lines) and control characters with visible markers.
+pub(crate) fn visible_whitespace(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '\n' => c.to_string(),
+        ' ' => "·".to_owned(),
+        '\t' => "→".to_owned(),
+        '\u{7f}' => "␡".to_owned(),
+        // control pictures, such as '␍' for '\r', are found at a fixed offset from C0 controls
+        c if (c as u32) < 0x20 => ::std::char::from_u32(0x2400 + c as u32).unwrap_or(c).to_string(),
+        c if c.is_whitespace() || c.is_control() => format!("\\u{{{:x}}}", c as u32),
+        c => c.to_string()
+    }).collect()
+}
+
+/// The ranges of lines to be shown, including each change and the unchanged lines around it.
+fn hunks(lines: &[DiffLine], context_lines: usize) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+
+    for (index, _) in lines.iter().enumerate().filter(|&(_, line)| line.is_change) {
+        let start = index.saturating_sub(context_lines);
+        let end = cmp::min(index + 1 + context_lines, lines.len());
+        match hunks.last_mut() {
+            Some(last) if last.1 >= start => last.1 = end,
+            _ => hunks.push((start, end))
+        }
+    }
+
+    if hunks.is_empty() {
+        hunks.push((0, lines.len()));
+    }
+
+    hunks
+}
+
+fn hunk_header(lines: &[DiffLine], start: usize, end: usize) -> String {
+    let line_counts = |range: &[DiffLine]| range.iter()
+        .fold((0, 0), |(old, new), line| (old + line.old_lines, new + line.new_lines));
+    let (old_before, new_before) = line_counts(&lines[..start]);
+    let (old_count, new_count) = line_counts(&lines[start..end]);
+    let first_line = |before: usize, count: usize| if count == 0 { before } else { before + 1 };
+
+    Cyan.paint(format!("@@ -{},{} +{},{} @@",
+                       first_line(old_before, old_count), old_count,
+                       first_line(new_before, new_count), new_count)).to_string()
+}
+
+fn unchanged_lines_marker(count: usize) -> String {
+    format!("... {} unchanged line{} ...", count, if count == 1 { "" } else { "s" })
+}
+
+fn line_diff(lines: &str, color: Option<Colour>, prefix: char) -> String {
+    let format_line = |line: &str| {
+        match color {
+            Option::Some(c) => c.paint(format!("{}{}", prefix, line)).to_string(),
+            Option::None => format!("{}{}", prefix, line)
+        }
+    };
+
+    lines.split('\n').map(format_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn word_by_word_diff(x: &str, y: &str, is_removal: bool) -> String {
+    let mut result = String::with_capacity(x.len() + y.len() + 20);
+    let line_diffs = Changeset::new(x, y, " ").diffs;
+    let base_color = if is_removal { Red } else { Green };
+    result.push_str(&base_color.paint(if is_removal { "-" } else { "+" }).to_string());
+    let mut line_diff_parts = Vec::with_capacity(line_diffs.len());
+    for diff in line_diffs {
+        match diff {
+            Difference::Same(ref z) => if !z.is_empty() {
+                line_diff_parts.push(base_color.paint(z.deref()).to_string());
+            },
+            Difference::Rem(ref z) => if !z.is_empty() && is_removal {
+                line_diff_parts.push(White.on(base_color).paint(z.deref()).to_string());
+            },
+            Difference::Add(ref z) => {
+                if !is_removal {
+                    line_diff_parts.push(White.on(base_color).paint(z.deref()).to_string());
+                }
+            }
+        }
+    }
+    result.push_str(&line_diff_parts.join(" "));
+    result
+}
+
+fn char_by_char_diff(x: &str, y: &str, is_removal: bool) -> String {
+    let old = x.chars().collect::<Vec<_>>();
+    let new = y.chars().collect::<Vec<_>>();
+    let base_color = if is_removal { Red } else { Green };
+    let paint = |segment: &str, is_highlighted: bool| if is_highlighted {
+        White.on(base_color).paint(segment).to_string()
+    } else {
+        base_color.paint(segment).to_string()
+    };
+
+    let mut result = String::with_capacity(x.len() + y.len() + 20);
+    result.push_str(&base_color.paint(if is_removal { "-" } else { "+" }).to_string());
+
+    // consecutive characters with the same highlighting are painted together
+    let mut segment = String::new();
+    let mut is_segment_highlighted = false;
+    for edit in diff_sequences(&old, &new) {
+        let (c, is_highlighted) = match edit {
+            Edit::Same(i, _) => (old[i], false),
+            Edit::Rem(i) if is_removal => (old[i], true),
+            Edit::Add(j) if !is_removal => (new[j], true),
+            _ => continue
+        };
+        if is_highlighted != is_segment_highlighted && !segment.is_empty() {
+            result.push_str(&paint(&segment, is_segment_highlighted));
+            segment.clear();
+        }
+        is_segment_highlighted = is_highlighted;
+        segment.push(c);
+    }
+    if !segment.is_empty() {
+        result.push_str(&paint(&segment, is_segment_highlighted));
+    }
+    result
+}
@@ -0,0 +1,77 @@
+use std::cmp;
+
+/// The line and column, in characters, of the first difference between two texts.
+///
+/// If one text is a prefix of the other, the difference is at the end of the shorter one.
+pub(crate) fn first_difference(x: &str, y: &str) -> (usize, usize) {
+    let (mut line, mut column) = (0, 0);
+    for (a, b) in x.chars().zip(y.chars()) {
+        if a != b {
+            break;
+        }
+        if a == '\n' {
+            line += 1;
+            column = 0;
+        } else {
+            column += 1;
+        }
+    }
+    (line, column)
+}
+
+/// Shorten a text exceeding the given limits, keeping the region around the given line and
+/// column visible and noting how much was omitted before and after it.
+pub(crate) fn elide(text: &str, (line, column): (usize, usize), max_line_length: usize, max_lines: usize) -> String {
+    let lines = text.split('\n').collect::<Vec<_>>();
+    let (start, end) = window(lines.len(), line, max_lines);
+
+    let mut result = Vec::with_capacity(end - start + 2);
+    if start > 0 {
+        result.push(omitted_lines(start));
+    }
+    for (index, text) in lines.iter().enumerate().take(end).skip(start) {
+        let column = if index == line { column } else { 0 };
+        result.push(elide_line(text, column, max_line_length));
+    }
+    if end < lines.len() {
+        result.push(omitted_lines(lines.len() - end));
+    }
+    result.join("\n")
+}
+
+/// Shorten a line longer than the given length, keeping the region around the given column.
+pub(crate) fn elide_line(line: &str, column: usize, max_line_length: usize) -> String {
+    if line.len() <= max_line_length {
+        return line.to_owned();
+    }
+    let chars = line.chars().collect::<Vec<_>>();
+    let (start, end) = window(chars.len(), column, max_line_length);
+
+    let mut result = String::with_capacity(max_line_length + 64);
+    if start > 0 {
+        result.push_str(&omitted_characters(start));
+    }
+    result.extend(&chars[start..end]);
+    if end < chars.len() {
+        result.push_str(&omitted_characters(chars.len() - end));
+    }
+    result
+}
+
+/// The range of at most `size` items, out of `count`, centered around the given position
+/// if possible.
+fn window(count: usize, position: usize, size: usize) -> (usize, usize) {
+    if count <= size {
+        return (0, count);
+    }
+    let start = cmp::min(position.saturating_sub(size / 2), count - size);
+    (start, start + size)
+}
+
+pub(crate) fn omitted_lines(count: usize) -> String {
+    format!("... {} line{} omitted ...", count, if count == 1 { "" } else { "s" })
+}
+
+fn omitted_characters(count: usize) -> String {
+    format!("[... {} character{} omitted ...]", count, if count == 1 { "" } else { "s" })
+}
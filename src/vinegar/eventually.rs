@@ -0,0 +1,75 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[doc(hidden)]
+pub fn internal_eventually<F>(timeout: Duration, interval: Duration, mut expectation: F) -> Result<(), String>
+    where F: FnMut() -> Result<(), String> {
+    let start = Instant::now();
+    let mut attempts = 0;
+
+    loop {
+        attempts += 1;
+        let err = match expectation() {
+            Ok(()) => return Ok(()),
+            Err(err) => err
+        };
+
+        let elapsed = start.elapsed();
+        if elapsed + interval > timeout {
+            return Err(format!("{}\n* Gave up after {} attempt{} in {:?} (timeout: {:?})\n",
+                               err.trim_end(), attempts, if attempts == 1 { "" } else { "s" },
+                               elapsed, timeout));
+        }
+
+        thread::sleep(interval);
+    }
+}
+
+///
+/// Create an expectation that is re-evaluated until it passes or the given timeout expires.
+///
+/// The expectation is evaluated immediately, then again after each `interval` has elapsed,
+/// for as long as it keeps failing and there is still time for another attempt within
+/// `timeout`. Both the timeout and the interval are `std::time::Duration` values.
+///
+/// If the expectation never passes, the failure of the last attempt is reported, followed by
+/// the number of attempts made and the total elapsed time.
+///
+/// This is useful to check the side effects of asynchronous code, such as background workers.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use std::sync::Arc;
+/// use std::thread;
+/// use std::time::Duration;
+/// use vinegar::vinegar::check;
+///
+/// let counter = Arc::new(AtomicUsize::new(0));
+/// let worker_counter = counter.clone();
+///
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_millis(20));
+///     worker_counter.store(10, Ordering::SeqCst);
+/// });
+///
+/// check(vec![
+///     eventually!(Duration::from_secs(2), Duration::from_millis(10),
+///                 expect!({ counter.load(Ordering::SeqCst) } == 10))
+/// ]);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! eventually {
+    ($timeout:expr, $interval:expr, $expectation:expr) => {{
+        $crate::vinegar::internal_eventually($timeout, $interval, || $expectation)
+    }};
+
+    ($timeout:expr, $interval:expr, $expectation:expr, $($arg:tt)+) => {{
+        $crate::eventually!($timeout, $interval, $expectation)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
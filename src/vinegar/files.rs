@@ -0,0 +1,199 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::fixture::Fixture;
+use super::get_diff;
+
+static TEMP_DIR_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory which is removed, with all of its contents, when dropped.
+///
+/// `TempDir` is also a [`Fixture`][fixture], so it can be used with
+/// [`with_fixture`][with_fixture].
+///
+/// [fixture]: trait.Fixture.html
+/// [with_fixture]: fn.with_fixture.html
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use std::fs;
+/// use vinegar::vinegar::{with_fixture, TempDir};
+///
+/// with_fixture(|dir: &mut TempDir| {
+///     fs::write(dir.path().join("hello.txt"), "Hello world").unwrap();
+///     vec![
+///         expect_file_exists!(dir.path().join("hello.txt")),
+///         expect_file_eq!(dir.path().join("hello.txt"), "Hello world"),
+///         expect_dir_contains!(dir.path(), ["hello.txt"]),
+///     ]
+/// });
+/// # }
+/// ```
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Create a new, empty, temporary directory under the system's temporary directory.
+    ///
+    /// # Panics
+    ///
+    /// If the directory cannot be created.
+    pub fn new() -> TempDir {
+        TempDir::try_new().unwrap_or_else(|err| panic!("Unable to create temporary directory: {}", err))
+    }
+
+    fn try_new() -> io::Result<TempDir> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|time| time.subsec_nanos())
+            .unwrap_or(0);
+        let name = format!("vinegar-{}-{}-{}", process::id(),
+                           TEMP_DIR_COUNTER.fetch_add(1, Ordering::SeqCst), nanos);
+        let path = env::temp_dir().join(name);
+        fs::create_dir(&path)?;
+        Ok(TempDir { path })
+    }
+
+    /// The path of this temporary directory.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Default for TempDir {
+    fn default() -> TempDir {
+        TempDir::new()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // errors are ignored as there's nothing useful to do about them at this point
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+impl Fixture for TempDir {
+    fn setup() -> TempDir {
+        TempDir::new()
+    }
+}
+
+#[doc(hidden)]
+pub fn internal_expect_file_exists<P>(path: &P, expr: &str) -> Result<(), String>
+    where P: AsRef<Path> + ?Sized {
+    let path = path.as_ref();
+    if path.is_file() {
+        Ok(())
+    } else {
+        Err(format!("* File does not exist: {}\n  Path: {}\n", expr, path.display()))
+    }
+}
+
+#[doc(hidden)]
+pub fn internal_expect_file_eq<P>(path: &P, expr: &str, expected: &str) -> Result<(), String>
+    where P: AsRef<Path> + ?Sized {
+    let path = path.as_ref();
+    match fs::read_to_string(path) {
+        Ok(ref contents) if contents == expected => Ok(()),
+        Ok(contents) => Err(format!("* File contents differ: {}\n  Path: {}\n{}",
+                                    expr, path.display(), get_diff(&contents, expected))),
+        Err(err) => Err(format!("* Unable to read file: {}\n  Path: {}\n  Cause: {}\n",
+                                expr, path.display(), err))
+    }
+}
+
+#[doc(hidden)]
+pub fn internal_expect_dir_contains<P>(path: &P, expr: &str, entries: &[&str]) -> Result<(), String>
+    where P: AsRef<Path> + ?Sized {
+    let path = path.as_ref();
+    let mut actual_entries = match fs::read_dir(path) {
+        Ok(dir) => dir.filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>(),
+        Err(err) => return Err(format!("* Unable to read directory: {}\n  Path: {}\n  Cause: {}\n",
+                                       expr, path.display(), err))
+    };
+    actual_entries.sort();
+
+    let missing = entries.iter()
+        .filter(|entry| !actual_entries.iter().any(|actual| actual == *entry))
+        .collect::<Vec<_>>();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("* Directory is missing entries: {}\n  Path: {}\n  \
+                     Missing: {:?}\n  Contents: {:?}\n",
+                    expr, path.display(), missing, actual_entries))
+    }
+}
+
+///
+/// Create an expectation that a file exists at the given path.
+///
+/// The path may be of any type implementing `AsRef<Path>`.
+///
+/// See [`TempDir`][temp_dir] for an example.
+///
+/// [temp_dir]: vinegar/struct.TempDir.html
+#[macro_export]
+macro_rules! expect_file_exists {
+    ($path:expr) => {{
+        $crate::vinegar::internal_expect_file_exists(&$path, stringify!($path))
+    }};
+
+    ($path:expr, $($arg:tt)+) => {{
+        $crate::expect_file_exists!($path)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that the file at the given path has exactly the expected contents.
+///
+/// If the contents differ, the difference between the actual and expected contents is shown.
+///
+/// See [`TempDir`][temp_dir] for an example.
+///
+/// [temp_dir]: vinegar/struct.TempDir.html
+#[macro_export]
+macro_rules! expect_file_eq {
+    ($path:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_file_eq(&$path, stringify!($path), &$expected)
+    }};
+
+    ($path:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_file_eq!($path, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+///
+/// Create an expectation that the directory at the given path contains all the given entries.
+///
+/// Entries are given by their file names, as an array, a slice or a `Vec` of `&str`, and may be
+/// files or directories, e.g. `expect_dir_contains!(dir, ["a.txt", "b.txt"], "after {} runs", runs)`.
+///
+/// See [`TempDir`][temp_dir] for an example.
+///
+/// [temp_dir]: vinegar/struct.TempDir.html
+#[macro_export]
+macro_rules! expect_dir_contains {
+    ($path:expr, $entries:expr) => {{
+        $crate::vinegar::internal_expect_dir_contains(&$path, stringify!($path), &$entries)
+    }};
+
+    ($path:expr, $entries:expr, $($arg:tt)+) => {{
+        $crate::expect_dir_contains!($path, $entries)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
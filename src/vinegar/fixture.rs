@@ -0,0 +1,82 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use super::check;
+
+/// A resource needed by a test, which must be set up before the test runs and torn down
+/// afterwards, such as a database or a directory.
+///
+/// Fixtures are used with [`with_fixture`][with_fixture], which guarantees that
+/// the fixture is torn down even if the test fails.
+///
+/// [with_fixture]: fn.with_fixture.html
+pub trait Fixture: Sized {
+    /// Create the fixture, acquiring any resources it needs.
+    fn setup() -> Self;
+
+    /// Release the resources held by the fixture.
+    ///
+    /// The default implementation does nothing.
+    fn teardown(self) {}
+}
+
+/// Run a test using a [`Fixture`][fixture], then check the expectations it returns.
+///
+/// The fixture is set up before the test runs, and is always torn down once the expectations
+/// have been evaluated, even if the test panics. Only then are the expectations verified with
+/// [`check`][check].
+///
+/// The type of fixture to use is given by the type of the closure's argument.
+///
+/// [fixture]: trait.Fixture.html
+/// [check]: fn.check.html
+///
+/// # Panics
+///
+/// If any expectation fails, or if the test itself panics.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::{with_fixture, Fixture};
+///
+/// struct Numbers(Vec<u32>);
+///
+/// impl Fixture for Numbers {
+///     fn setup() -> Numbers {
+///         Numbers(vec![1, 2, 3])
+///     }
+///
+///     fn teardown(mut self) {
+///         self.0.clear();
+///     }
+/// }
+///
+/// with_fixture(|numbers: &mut Numbers| {
+///     numbers.0.push(4);
+///     vec![
+///         expect!({ numbers.0.len() } == 4),
+///         expect!({ numbers.0[3] } == 4),
+///     ]
+/// });
+/// # }
+/// ```
+pub fn with_fixture<F, T, I>(test: T)
+    where F: Fixture,
+          T: FnOnce(&mut F) -> I,
+          I: IntoIterator<Item=Result<(), String>> {
+    let mut fixture = F::setup();
+
+    // expectations may be lazily evaluated, so they must be collected before the teardown
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        test(&mut fixture).into_iter().collect::<Vec<_>>()
+    }));
+
+    fixture.teardown();
+
+    match result {
+        Ok(expects) => check(expects),
+        Err(cause) => panic::resume_unwind(cause)
+    }
+}
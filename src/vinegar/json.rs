@@ -0,0 +1,167 @@
+use std::collections::BTreeSet;
+
+use serde_json::{self, Value};
+
+use super::diff_options;
+use super::elide::elide_line;
+
+///
+/// Create an expectation that two JSON values are semantically equal, regardless of the order of
+/// object keys and of whitespace.
+///
+/// Each value may be a JSON text, as a `&str` or `String`, or a `serde_json::Value`. If they
+/// differ, each difference is reported with its JSON path, e.g. `$.items[3].price: 10 != 12`,
+/// showing the actual value before the expected one. Numbers are compared by value, so that `1`
+/// and `1.0` are equal.
+///
+/// If `ignore_extra_fields` is given after both values, fields of objects in the actual value
+/// which are not in the expected value are ignored.
+///
+/// This macro is only available with the `serde_json` feature.
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::check;
+///
+/// let response = r#"{"name": "Joe", "id": 42, "roles": ["admin"]}"#;
+/// check(vec![
+///     expect_json_eq!(response, r#"{"id": 42, "roles": ["admin"], "name": "Joe"}"#),
+///     expect_json_eq!(response, r#"{"name": "Joe"}"#, ignore_extra_fields),
+/// ]);
+/// # }
+/// ```
+///
+/// The macro can also be called through its path, with a custom message:
+///
+/// ```edition2018
+/// vinegar::vinegar::check(vec![
+///     vinegar::expect_json_eq!(r#"{"id": 42}"#, r#"{"id": 42.0}"#, "for user {}", "Joe"),
+///     vinegar::expect_json_eq!(r#"{"id": 42, "x": 1}"#, r#"{"id": 42}"#, ignore_extra_fields, "for user {}", "Joe"),
+/// ]);
+/// ```
+#[macro_export]
+macro_rules! expect_json_eq {
+    ($actual:expr, $expected:expr) => {{
+        $crate::vinegar::internal_expect_json_eq(&$actual, stringify!($actual), &$expected, stringify!($expected), false)
+    }};
+
+    ($actual:expr, $expected:expr, ignore_extra_fields) => {{
+        $crate::vinegar::internal_expect_json_eq(&$actual, stringify!($actual), &$expected, stringify!($expected), true)
+    }};
+
+    ($actual:expr, $expected:expr, ignore_extra_fields, $($arg:tt)+) => {{
+        $crate::expect_json_eq!($actual, $expected, ignore_extra_fields)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }};
+
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {{
+        $crate::expect_json_eq!($actual, $expected)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }}
+}
+
+/// A value which can be compared as JSON by `expect_json_eq`.
+#[doc(hidden)]
+pub trait InternalJson {
+    fn internal_to_json(&self) -> Result<Value, serde_json::Error>;
+}
+
+impl InternalJson for str {
+    fn internal_to_json(&self) -> Result<Value, serde_json::Error> {
+        serde_json::from_str(self)
+    }
+}
+
+impl InternalJson for String {
+    fn internal_to_json(&self) -> Result<Value, serde_json::Error> {
+        serde_json::from_str(self)
+    }
+}
+
+impl InternalJson for Value {
+    fn internal_to_json(&self) -> Result<Value, serde_json::Error> {
+        Ok(self.clone())
+    }
+}
+
+impl<T: InternalJson + ?Sized> InternalJson for &T {
+    fn internal_to_json(&self) -> Result<Value, serde_json::Error> {
+        (**self).internal_to_json()
+    }
+}
+
+#[doc(hidden)]
+pub fn internal_expect_json_eq<A, E>(actual: &A, actual_expr: &str, expected: &E, expected_expr: &str,
+                                     ignore_extra_fields: bool) -> Result<(), String>
+    where A: InternalJson + ?Sized, E: InternalJson + ?Sized {
+    let parse = |value: &dyn InternalJson, expr: &str| value.internal_to_json()
+        .map_err(|err| format!("* Invalid JSON: {}\n  Cause: {}\n", expr, err));
+    let actual = parse(&actual, actual_expr)?;
+    let expected = parse(&expected, expected_expr)?;
+
+    let mut differences = Vec::new();
+    compare("$".to_owned(), &actual, &expected, ignore_extra_fields, &mut differences);
+
+    if differences.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("* JSON values differ: {} and {}\n{}", actual_expr, expected_expr,
+                    differences.iter().map(|difference| format!("  {}\n", difference)).collect::<String>()))
+    }
+}
+
+/// Collect the differences between two JSON values, each described with the path where it occurs.
+fn compare(path: String, actual: &Value, expected: &Value, ignore_extra_fields: bool, differences: &mut Vec<String>) {
+    match (actual, expected) {
+        (Value::Object(actual), Value::Object(expected)) => {
+            let keys = actual.keys().chain(expected.keys()).collect::<BTreeSet<_>>();
+            for key in keys {
+                let path = format!("{}{}", path, key_path(key));
+                match (actual.get(key), expected.get(key)) {
+                    (Some(a), Some(e)) => compare(path, a, e, ignore_extra_fields, differences),
+                    (Some(a), None) => if !ignore_extra_fields {
+                        differences.push(format!("{}: unexpected field with value {}", path, show(a)));
+                    },
+                    (None, Some(e)) => differences.push(format!("{}: missing field with value {}", path, show(e))),
+                    (None, None) => {}
+                }
+            }
+        }
+        (Value::Array(actual), Value::Array(expected)) => {
+            for (index, (a, e)) in actual.iter().zip(expected.iter()).enumerate() {
+                compare(format!("{}[{}]", path, index), a, e, ignore_extra_fields, differences);
+            }
+            for (index, a) in actual.iter().enumerate().skip(expected.len()) {
+                differences.push(format!("{}[{}]: unexpected element {}", path, index, show(a)));
+            }
+            for (index, e) in expected.iter().enumerate().skip(actual.len()) {
+                differences.push(format!("{}[{}]: missing element {}", path, index, show(e)));
+            }
+        }
+        // integers and floats are compared as floats, as JSON does not distinguish them
+        (Value::Number(a), Value::Number(e)) if a.is_f64() || e.is_f64() => if a.as_f64() != e.as_f64() {
+            differences.push(format!("{}: {} != {}", path, show(actual), show(expected)));
+        },
+        _ => if actual != expected {
+            differences.push(format!("{}: {} != {}", path, show(actual), show(expected)));
+        }
+    }
+}
+
+/// The path of an object key, using the dot notation unless the key is not a valid identifier.
+fn key_path(key: &str) -> String {
+    let is_identifier = key.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_alphanumeric() || c == '_');
+    if is_identifier {
+        format!(".{}", key)
+    } else {
+        format!("[{}]", Value::String(key.to_owned()))
+    }
+}
+
+fn show(value: &Value) -> String {
+    elide_line(&value.to_string(), 0, diff_options().max_line_length)
+}
@@ -0,0 +1,131 @@
+///
+/// Generate a mock implementation of a trait.
+///
+/// Given the name of the mock struct, the trait it implements and the signatures of the trait's
+/// methods, this macro generates a struct with one public [`Spy`][spy] field per method, named
+/// after the method. Each trait method delegates to its spy, so every call is recorded.
+///
+/// The value returned by a method is configured through its spy, with `returns(value)` or
+/// `will(closure)`, where the closure receives a tuple with the method arguments.
+/// Methods without a return type do nothing by default, while calling a method returning a value
+/// that has not been configured panics.
+///
+/// Calls can be verified with the spy expectations, such as `called_times` and `called_with`,
+/// which can be checked with [`check`][check] together with any other expectations.
+///
+/// Only methods taking `&self` or `&mut self` are supported. Method arguments must be owned
+/// values (no references) implementing `Clone`, `Debug` and `PartialEq`.
+///
+/// [spy]: vinegar/struct.Spy.html
+/// [check]: vinegar/fn.check.html
+///
+/// # Examples
+///
+/// ```
+/// # #[macro_use] extern crate vinegar;
+/// # fn main() {
+/// use vinegar::vinegar::check;
+///
+/// trait Greeter {
+///     fn greet(&self, name: String) -> String;
+///     fn reset(&mut self);
+/// }
+///
+/// mock! {
+///     MockGreeter: Greeter {
+///         fn greet(&self, name: String) -> String;
+///         fn reset(&mut self);
+///     }
+/// }
+///
+/// let greeter = MockGreeter::new();
+/// greeter.greet.will(|(name,)| format!("Hello {}", name));
+///
+/// check(vec![
+///     expect!({ greeter.greet("Joe".to_owned()) } == "Hello Joe"),
+///     greeter.greet.called_with(&("Joe".to_owned(),)),
+///     greeter.reset.never_called(),
+/// ]);
+/// # }
+/// ```
+///
+/// The macro can also be called through its path, without `#[macro_use]`:
+///
+/// ```edition2018
+/// trait Clock {
+///     fn now(&self) -> u64;
+/// }
+///
+/// vinegar::mock! {
+///     MockClock: Clock {
+///         fn now(&self) -> u64;
+///     }
+/// }
+///
+/// let clock = MockClock::new();
+/// clock.now.returns(42);
+/// assert_eq!(clock.now(), 42);
+/// ```
+#[macro_export]
+macro_rules! mock {
+    ($vis:vis $mock:ident : $tr:path {
+        $(fn $method:ident(&$($receiver:ident)+ $(, $arg:ident : $arg_type:ty)*) $(-> $ret:ty)?;)*
+    }) => {
+        $vis struct $mock {
+            $(pub $method: $crate::vinegar::Spy<($($arg_type,)*), $crate::internal_mock_return_type!($($ret)?)>,)*
+        }
+
+        impl $mock {
+            /// Create a new mock with the default behaviour for every method.
+            pub fn new() -> $mock {
+                $mock {
+                    $($method: $crate::vinegar::Spy::named(
+                        concat!(stringify!($mock), "::", stringify!($method)),
+                        $crate::internal_mock_default_behaviour!(
+                            concat!(stringify!($mock), "::", stringify!($method)); $($ret)?)),)*
+                }
+            }
+        }
+
+        impl Default for $mock {
+            fn default() -> $mock {
+                $mock::new()
+            }
+        }
+
+        impl $tr for $mock {
+            $($crate::internal_mock_method!($($receiver)+; $method($($arg: $arg_type),*) $(-> $ret)?);)*
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! internal_mock_method {
+    (self; $method:ident($($arg:ident : $arg_type:ty),*) $(-> $ret:ty)?) => {
+        fn $method(&self $(, $arg: $arg_type)*) -> $crate::internal_mock_return_type!($($ret)?) {
+            self.$method.call(($($arg,)*))
+        }
+    };
+    (mut self; $method:ident($($arg:ident : $arg_type:ty),*) $(-> $ret:ty)?) => {
+        fn $method(&mut self $(, $arg: $arg_type)*) -> $crate::internal_mock_return_type!($($ret)?) {
+            self.$method.call(($($arg,)*))
+        }
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! internal_mock_return_type {
+    () => { () };
+    ($ret:ty) => { $ret };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! internal_mock_default_behaviour {
+    ($name:expr;) => { |_| () };
+    ($name:expr; $ret:ty) => {
+        |_| -> $ret { panic!("No return value configured for {}", $name) }
+    };
+}
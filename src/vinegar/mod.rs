@@ -1,9 +1,52 @@
-use difference::Changeset;
-use difference::Difference;
-use ansi_term::Colour;
-use ansi_term::Colour::{Green, Red, White};
-use std::string::ToString;
-use std::ops::Deref;
+use std::cmp;
+
+use self::diff::{get_diff, needs_visible_whitespace, visible_whitespace};
+use self::elide::{elide, first_difference};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+#[macro_use]
+mod bytes;
+#[cfg(unix)]
+#[macro_use]
+mod capture;
+mod check;
+mod cmd;
+#[macro_use]
+mod collections;
+mod diff;
+mod elide;
+#[macro_use]
+mod eventually;
+#[macro_use]
+mod files;
+#[cfg(feature = "serde_json")]
+#[macro_use]
+mod json;
+mod fixture;
+#[macro_use]
+mod mock;
+mod power;
+mod side_by_side;
+mod spy;
+
+pub use self::bytes::internal_expect_bytes_eq;
+#[cfg(unix)]
+pub use self::capture::{capture_output, CapturedOutput, internal_expect_stderr_contains, internal_expect_stderr_eq,
+                        internal_expect_stdout_contains, internal_expect_stdout_eq};
+pub use self::check::{check, check_each, check_options, check_parallel, set_check_options, CheckOptions};
+pub use self::cmd::{Cmd, CmdResult};
+pub use self::collections::{internal_expect_map_eq, internal_expect_seq_eq, internal_expect_set_eq, InternalMap,
+                            InternalSet};
+pub use self::diff::{diff_options, set_diff_options, DiffLayout, DiffOptions, InlineDiff};
+pub use self::eventually::internal_eventually;
+pub use self::files::{internal_expect_dir_contains, internal_expect_file_eq, internal_expect_file_exists, TempDir};
+#[cfg(feature = "serde_json")]
+pub use self::json::{internal_expect_json_eq, InternalJson};
+pub use self::fixture::{with_fixture, Fixture};
+pub use self::power::{internal_power_build_error, InternalDebugValue, InternalOtherValue, InternalValue};
+pub use self::spy::Spy;
+pub use vinegar_macros::internal_power_expect;
 
 enum ValuesToPrint {
     Both,
@@ -15,172 +58,16 @@ enum ValuesToPrint {
 
 impl ValuesToPrint {
     fn has_first(&self) -> bool {
-        match *self {
-            ValuesToPrint::Both | ValuesToPrint::First => true,
-            _ => false
-        }
+        matches!(*self, ValuesToPrint::Both | ValuesToPrint::First)
     }
 
     fn has_second(&self) -> bool {
-        match *self {
-            ValuesToPrint::Both | ValuesToPrint::Second => true,
-            _ => false,
-        }
+        matches!(*self, ValuesToPrint::Both | ValuesToPrint::Second)
     }
 
     fn has_both(&self) -> bool {
-        match *self {
-            ValuesToPrint::Both => true,
-            _ => false
-        }
-    }
-}
-
-/// Check whether the given expectations have been met successfully.
-///
-/// # Panics
-///
-/// If any expectation fails, this function panics with an error message showing why each
-/// expectation failed.
-pub fn check<I>(expects: I)
-    where I: IntoIterator<Item=Result<(), String>> {
-    let mut failures = Vec::new();
-
-    for (index, expect) in expects.into_iter().enumerate() {
-        if let Err(err) = expect {
-            failures.push(format!("iteration[{}]:\n{}", index, err));
-        }
-    }
-
-    if !failures.is_empty() {
-        panic!("\n{}", failures.join("\n"));
-    }
-}
-
-fn get_diff(text1: &str, text2: &str) -> String {
-    enum SecondIteration {
-        SkipWithNewLine,
-        SkipNoNewLine,
-        NoNewLine,
-        WithNewLine
-    }
-
-    let differences = Changeset::new(text1, text2, "\n").diffs;
-    let diff_pairs = differences.windows(2);
-    let mut result = String::with_capacity(text1.len() + text2.len());
-    let mut second_iteration: SecondIteration;
-
-    result.push_str("----- Difference -----\n");
-
-    for diff_pair in diff_pairs {
-        let prev = &diff_pair[0];
-        let current = &diff_pair[1];
-
-        match *prev {
-            Difference::Same(ref x) => if x.is_empty() {
-                second_iteration = SecondIteration::SkipNoNewLine;
-            } else {
-                result.push_str(&line_diff(&x, Option::None, ' '));
-                second_iteration = SecondIteration::SkipWithNewLine;
-            },
-            Difference::Rem(ref x) => {
-                if x.contains('\n') {
-                    // several lines included in Rem, show them without word-by-word diff
-                    result.push_str(&line_diff(&x, Option::Some(Red), '-'));
-                } else {
-                    // show word-by-word diff
-                    match *current {
-                        Difference::Add(ref y) => {
-                            result.push_str(&word_by_word_diff(x, y, true));
-                        }
-                        _ => {
-                            result.push_str(&line_diff(&x, Option::Some(Red), '-'));
-                        }
-                    }
-                }
-                second_iteration = SecondIteration::WithNewLine;
-            }
-            Difference::Add(_) => {
-                second_iteration = SecondIteration::NoNewLine;
-            }
-        }
-
-        match second_iteration {
-            SecondIteration::SkipWithNewLine => {
-                result.push('\n');
-                continue
-            }
-            SecondIteration::SkipNoNewLine => continue,
-            SecondIteration::NoNewLine => (),
-            SecondIteration::WithNewLine => result.push('\n')
-        }
-
-        match *current {
-            Difference::Same(_) => (),
-            Difference::Add(ref x) => {
-                if x.contains('\n') {
-                    // several lines included in Rem, show them without word-by-word diff
-                    result.push_str(&line_diff(&x, Option::Some(Green), '+'));
-                } else {
-                    // show word-by-word diff
-                    match *prev {
-                        Difference::Rem(ref y) => {
-                            result.push_str(&word_by_word_diff(y, x, false));
-                        }
-                        _ => {
-                            result.push_str(&line_diff(&x, Option::Some(Green), '+'));
-                        }
-                    }
-                }
-                result.push('\n');
-            }
-            Difference::Rem(_) => ()
-        }
-    }
-
-    result.push_str("----------------------\n");
-
-    result
-}
-
-fn line_diff(lines: &str, color: Option<Colour>, prefix: char) -> String {
-    let format_line = |line: &str| {
-        match color {
-            Option::Some(c) => c.paint(format!("{}{}", prefix, line)).to_string(),
-            Option::None => format!("{}{}", prefix, line)
-        }
-    };
-
-    lines.split('\n').map(format_line)
-        .collect::<Vec<_>>()
-        .join("\n")
-}
-
-fn word_by_word_diff(x: &str, y: &str, is_removal: bool) -> String {
-    let mut result = String::with_capacity(x.len() + y.len() + 20);
-    let line_diffs = Changeset::new(x, y, " ").diffs;
-    let base_color = if is_removal { Red } else { Green };
-    result.push_str(&base_color.paint(if is_removal { "-" } else { "+" }).to_string());
-    let mut line_diff_parts = Vec::with_capacity(line_diffs.len());
-    for diff in line_diffs {
-        match diff {
-            Difference::Same(ref z) => if !z.is_empty() {
-                line_diff_parts.push(base_color.paint(z.deref()).to_string());
-            },
-            Difference::Rem(ref z) => if !z.is_empty() {
-                if is_removal {
-                    line_diff_parts.push(White.on(base_color).paint(z.deref()).to_string());
-                }
-            },
-            Difference::Add(ref z) => {
-                if !is_removal {
-                    line_diff_parts.push(White.on(base_color).paint(z.deref()).to_string());
-                }
-            }
-        }
+        matches!(*self, ValuesToPrint::Both)
     }
-    result.push_str(&line_diff_parts.join(" "));
-    result
 }
 
 #[doc(hidden)]
@@ -193,19 +80,32 @@ pub fn internal_build_error(val1: &str, expr1: &str, op: &str, val2: &str, expr2
         if expr2 == val2 { ValuesToPrint::First } else { ValuesToPrint::Both }
     };
 
+    // invisible characters are shown when they are likely to be the cause of the failure
+    let (shown_val1, shown_val2) = if needs_visible_whitespace(val1, val2) {
+        (visible_whitespace(val1), visible_whitespace(val2))
+    } else {
+        (val1.to_owned(), val2.to_owned())
+    };
+
+    // huge values are shortened, keeping their first difference visible
+    let options = diff_options();
+    let position = first_difference(&shown_val1, &shown_val2);
+    let shown_val1 = elide(&shown_val1, position, options.max_line_length, options.max_lines);
+    let shown_val2 = elide(&shown_val2, position, options.max_line_length, options.max_lines);
+
     let spaces = " ".repeat(intro.len());
     // quotes are rendered with an escape character, so we need to add to the length
-    let expr1_len = expr1.len();
+    let expr1_len = display_width(expr1);
     let val1_underlines = (if values_to_print.has_first() { "-" } else { " " }).repeat(expr1_len);
     let val1_arrow_spaces = " ".repeat(expr1_len / 2);
     let val1_arrow = if values_to_print.has_first() { "|" } else { " " };
 
-    let expr2_len = expr2.len();
+    let expr2_len = display_width(expr2);
     let val2_underlines = (if values_to_print.has_second() { "-" } else { " " }).repeat(expr2_len);
     let val2_arrow_spaces = " ".repeat(expr2_len / 2);
     let val2_arrow = if values_to_print.has_second() { "|" } else { " " };
 
-    let op_spaces = " ".repeat(op.len() + 2);
+    let op_spaces = " ".repeat(display_width(op) + 2);
 
     let last_lines_prefix = format!("{}{}", spaces, val1_arrow_spaces);
     let first_lines_prefix = format!("{}{}{}{}{}", last_lines_prefix, val1_arrow,
@@ -214,7 +114,7 @@ pub fn internal_build_error(val1: &str, expr1: &str, op: &str, val2: &str, expr2
     let underlines_line = format!("{}{}{}{}\n", spaces, val1_underlines, op_spaces, val2_underlines);
     let both_arrows_line = format!("{}{}\n", first_lines_prefix, val2_arrow);
     let val2_lines = if values_to_print.has_second() {
-        format!("{}\n", val2.split('\n')
+        format!("{}\n", shown_val2.split('\n')
             .map(|line| format!("{}{}", first_lines_prefix, line))
             .collect::<Vec<_>>()
             .join("\n"))
@@ -227,7 +127,7 @@ pub fn internal_build_error(val1: &str, expr1: &str, op: &str, val2: &str, expr2
         String::new()
     };
     let val1_lines = if values_to_print.has_first() {
-        format!("{}\n", val1.split('\n')
+        format!("{}\n", shown_val1.split('\n')
             .map(|line| format!("{}{}", last_lines_prefix, line))
             .collect::<Vec<_>>()
             .join("\n"))
@@ -242,6 +142,19 @@ pub fn internal_build_error(val1: &str, expr1: &str, op: &str, val2: &str, expr2
             both_arrows_line, val2_lines, line4, val1_lines, error_diff)
 }
 
+/// Add a custom message, given to an expectation macro with `format!`-style arguments, under
+/// the error message of a failed expectation.
+#[doc(hidden)]
+pub fn internal_with_message(error: String, message: String) -> String {
+    let message = message.lines().map(|line| format!("  {}\n", line)).collect::<String>();
+    format!("{}{}", error, message)
+}
+
+/// The number of columns taken by the given text in a terminal, where each grapheme cluster
+/// takes at most two columns (e.g. East Asian wide characters and emoji sequences).
+pub(crate) fn display_width(text: &str) -> usize {
+    text.graphemes(true).map(|grapheme| cmp::min(grapheme.width(), 2)).sum()
+}
 
 ///
 /// Create a general expectation that can be checked with [`check`][check].
@@ -253,6 +166,14 @@ pub fn internal_build_error(val1: &str, expr1: &str, op: &str, val2: &str, expr2
 /// which makes it much easier to understand why a test may have failed.
 /// Also, if the `==` operator is used, a diff between the values can be shown.
 ///
+/// Any other boolean expression, such as `expect!(items.len() + offset > limit)`, is evaluated
+/// only once, and the value of each of its sub-expressions is shown under it, power-assert
+/// style, if it fails (see `vinegar_macros::expect`).
+///
+/// The condition may be followed by `format!`-style arguments, as in
+/// `expect!({ total } == 10, "for user {}", user)`, which are only evaluated if it fails, and
+/// printed under the error message. All the other expectation macros accept them too.
+///
 /// [check]: vinegar/fn.check.html
 ///
 /// # Examples
@@ -268,21 +189,33 @@ pub fn internal_build_error(val1: &str, expr1: &str, op: &str, val2: &str, expr2
 ///     expect!("Hello world" == { format!("{} {}", "Hello", "world") })
 /// ]);
 /// # }
+/// ```
 ///
+/// The expectation macros can also be called through their path, without `#[macro_use]`:
+///
+/// ```edition2018
+/// let total = 4;
+/// vinegar::vinegar::check(vec![
+///     vinegar::expect!({ total } == 4, "total for user {}", "Joe"),
+///     vinegar::expect_eq!(total, 4, "total for user {}", "Joe"),
+/// ]);
+/// ```
 #[macro_export]
 macro_rules! expect {
 
-    ($b:block $op:tt $a:block) => {{
-        if $b $op $a {
+    // a block compared to an expression, which must be matched before the arms expecting a block
+    // on the right side, as a forwarded expression cannot be matched as a block
+    (@block_expr $b:block $op:tt $($a:tt)+) => {{
+        if $b $op $($a)* {
             Result::Ok(())
         } else {
             Result::Err($crate::vinegar::internal_build_error(
                 &format!("{}", $b), stringify!($b), stringify!($op),
-                &format!("{}", $a), stringify!($a)))
+                &format!("{}", $($a)*), stringify!($($a)*)))
         }
     }};
 
-    ($b:tt $op:tt $a:block) => {{
+    ($b:block $op:tt $a:block) => {{
         if $b $op $a {
             Result::Ok(())
         } else {
@@ -292,22 +225,31 @@ macro_rules! expect {
         }
     }};
 
-    ($b:block $op:tt $($a:tt)+) => {{
-        if $b $op $($a)* {
+    ($b:tt $op:tt $a:block) => {{
+        if $b $op $a {
             Result::Ok(())
         } else {
             Result::Err($crate::vinegar::internal_build_error(
                 &format!("{}", $b), stringify!($b), stringify!($op),
-                &format!("{}", $($a)*), stringify!($($a)*)))
+                &format!("{}", $a), stringify!($a)))
         }
     }};
 
+    ($b:tt $op:tt $a:block, $($arg:tt)+) => {{
+        $crate::expect!($b $op $a).map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }};
+
+    ($b:block $op:tt $a:expr, $($arg:tt)+) => {{
+        $crate::expect!(@block_expr $b $op $a)
+            .map_err(|error| $crate::vinegar::internal_with_message(error, format!($($arg)+)))
+    }};
+
+    ($b:block $op:tt $($a:tt)+) => {{
+        $crate::expect!(@block_expr $b $op $($a)+)
+    }};
+
     ($($a:tt)*) => {{
-        if $($a)* {
-            Result::Ok(())
-        } else {
-            Result::Err(format!("Condition failed: {}", stringify!($($a)*)))
-        }
+        $crate::vinegar::internal_power_expect!($crate::vinegar; $($a)*)
     }};
 
 }
@@ -334,6 +276,10 @@ macro_rules! expect {
 #[macro_export]
 macro_rules! expect_eq {
     ($a:expr, $b: expr) => {{
-        expect!({ $a } == { $b })
+        $crate::expect!({ $a } == { $b })
+    }};
+
+    ($a:expr, $b: expr, $($arg:tt)+) => {{
+        $crate::expect!({ $a } == { $b }, $($arg)+)
     }}
 }
@@ -0,0 +1,125 @@
+use std::any::type_name;
+use std::fmt::Debug;
+
+use super::diff_options;
+use super::display_width;
+use super::elide::elide_line;
+
+/// A reference to a value recorded by a power-assert expectation, which is shown with its `Debug`
+/// implementation if it has one, or with its type name otherwise.
+#[doc(hidden)]
+pub struct InternalValue<'a, T: 'a + ?Sized>(pub &'a T);
+
+#[doc(hidden)]
+pub trait InternalDebugValue {
+    fn internal_describe(&self) -> String;
+}
+
+impl<'a, T: Debug + ?Sized> InternalDebugValue for InternalValue<'a, T> {
+    fn internal_describe(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// Only used for values not implementing `Debug`, as method resolution prefers the implementation
+/// of `InternalDebugValue`, which does not need an extra reference.
+#[doc(hidden)]
+pub trait InternalOtherValue {
+    fn internal_describe(&self) -> String;
+}
+
+impl<'a, 'b, T: ?Sized> InternalOtherValue for &'b InternalValue<'a, T> {
+    fn internal_describe(&self) -> String {
+        format!("<{}>", short_type_name(type_name::<T>()))
+    }
+}
+
+/// Remove the module paths from a type name, e.g. `alloc::vec::Vec<app::User>` becomes `Vec<User>`.
+fn short_type_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    let mut segment_start = 0;
+    let mut chars = name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            result.truncate(segment_start);
+        } else {
+            if !(c.is_alphanumeric() || c == '_') {
+                segment_start = result.len() + c.len_utf8();
+            }
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// Build the error message of a failed power-assert expectation, showing each value under the
+/// sub-expression at the given offset of the condition's source text.
+///
+/// If the condition is made of clauses joined by `&&` or `||`, the start and end offsets of each
+/// clause are given with its result, if it was evaluated.
+#[doc(hidden)]
+pub fn internal_power_build_error(text: &str, values: &[(usize, String)],
+                                  clauses: &[(usize, usize, Option<bool>)]) -> String {
+    let intro = "* Condition failed: ";
+    let options = diff_options();
+
+    let mut columns: Vec<(usize, String)> = Vec::with_capacity(values.len());
+    for &(offset, ref value) in values {
+        let column = intro.len() + display_width(&text[..offset]);
+        let value = elide_line(&value.replace('\n', " "), 0, options.max_line_length);
+        // outer expressions are evaluated last, and their values replace the inner ones
+        columns.retain(|&(other, _)| other != column);
+        columns.push((column, value));
+    }
+    columns.sort_by_key(|&(column, _)| column);
+
+    let mut lines = Vec::new();
+    if !columns.is_empty() {
+        lines.push(row(&columns.iter().map(|&(column, _)| (column, "|".to_owned())).collect::<Vec<_>>()));
+    }
+
+    // values are placed from right to left, on the first line where they fit
+    let mut pending = columns;
+    while !pending.is_empty() {
+        let mut line = Vec::with_capacity(pending.len());
+        let mut remaining = Vec::new();
+        let mut limit = usize::MAX;
+        for (column, value) in pending.into_iter().rev() {
+            if column + display_width(&value) < limit {
+                line.push((column, value));
+            } else {
+                line.push((column, "|".to_owned()));
+                remaining.push((column, value));
+            }
+            limit = column;
+        }
+        line.reverse();
+        remaining.reverse();
+        lines.push(row(&line));
+        pending = remaining;
+    }
+
+    if !clauses.is_empty() {
+        lines.push("  Clauses:".to_owned());
+        let width = clauses.iter().map(|&(start, end, _)| display_width(&text[start..end])).max().unwrap_or(0);
+        for &(start, end, result) in clauses {
+            let clause = &text[start..end];
+            let result = result.map(|result| result.to_string()).unwrap_or_else(|| "not evaluated".to_owned());
+            lines.push(format!("    {}{} => {}", clause, " ".repeat(width - display_width(clause)), result));
+        }
+    }
+
+    format!("{}{}\n{}", intro, text, lines.iter().map(|line| format!("{}\n", line)).collect::<String>())
+}
+
+fn row(items: &[(usize, String)]) -> String {
+    let mut result = String::new();
+    let mut width = 0;
+    for &(column, ref text) in items {
+        result.push_str(&" ".repeat(column - width));
+        result.push_str(text);
+        width = column + display_width(text);
+    }
+    result
+}
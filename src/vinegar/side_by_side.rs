@@ -0,0 +1,221 @@
+use std::cmp;
+use std::env;
+
+use ansi_term::Colour;
+use ansi_term::Colour::{Green, Red, White};
+use unicode_width::UnicodeWidthChar;
+
+use super::diff::{diff_sequences, has_control_characters, needs_visible_whitespace, visible_whitespace,
+                  DiffLine, Edit, InlineDiff};
+use super::display_width;
+
+/// The width used for side-by-side diffs when the width of the terminal is unknown.
+const DEFAULT_WIDTH: usize = 120;
+
+/// The minimum width of each column of a side-by-side diff.
+const MIN_COLUMN_WIDTH: usize = 10;
+
+/// A line of text in which each character may be highlighted.
+type Highlighted = Vec<(char, bool)>;
+
+/// The width of the terminal, in columns.
+pub(crate) fn terminal_width() -> usize {
+    env::var("COLUMNS").ok()
+        .and_then(|columns| columns.trim().parse().ok())
+        .or_else(tty_width)
+        .unwrap_or(DEFAULT_WIDTH)
+}
+
+#[cfg(unix)]
+fn tty_width() -> Option<usize> {
+    use libc;
+
+    [libc::STDOUT_FILENO, libc::STDERR_FILENO].iter().filter_map(|&fd| {
+        let mut size: libc::winsize = unsafe { ::std::mem::zeroed() };
+        let result = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+        if result == 0 && size.ws_col > 0 { Some(size.ws_col as usize) } else { None }
+    }).next()
+}
+
+#[cfg(not(unix))]
+fn tty_width() -> Option<usize> {
+    None
+}
+
+pub(crate) fn side_by_side_lines(old: &[&str], new: &[&str], edits: &[Edit], inline_diff: InlineDiff,
+                                 width: usize) -> Vec<DiffLine> {
+    let column_width = cmp::max(width.saturating_sub(3) / 2, MIN_COLUMN_WIDTH);
+    let mut lines = Vec::with_capacity(edits.len());
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+
+    for edit in edits {
+        match *edit {
+            Edit::Same(i, _) => {
+                push_changes(&mut lines, &mut removed, &mut added, inline_diff, column_width);
+                let line = if has_control_characters(old[i]) { visible_whitespace(old[i]) } else { old[i].to_owned() };
+                let line = plain(&line);
+                lines.push(DiffLine {
+                    text: row(&line, &line, ' ', None, None, column_width),
+                    is_change: false,
+                    old_lines: 1,
+                    new_lines: 1,
+                });
+            }
+            Edit::Rem(i) => removed.push(old[i]),
+            Edit::Add(j) => added.push(new[j])
+        }
+    }
+    push_changes(&mut lines, &mut removed, &mut added, inline_diff, column_width);
+
+    lines
+}
+
+fn push_changes(lines: &mut Vec<DiffLine>, removed: &mut Vec<&str>, added: &mut Vec<&str>,
+                inline_diff: InlineDiff, column_width: usize) {
+    let show_invisible = needs_visible_whitespace(&removed.join("\n"), &added.join("\n"));
+    let visible = |line: &&str| if show_invisible { visible_whitespace(line) } else { line.to_string() };
+    let removed_lines = removed.iter().map(visible).collect::<Vec<_>>();
+    let added_lines = added.iter().map(visible).collect::<Vec<_>>();
+
+    // the first removed lines are shown next to the first added lines
+    for index in 0..cmp::max(removed_lines.len(), added_lines.len()) {
+        let line = match (removed_lines.get(index), added_lines.get(index)) {
+            (Some(x), Some(y)) => {
+                let by_characters = show_invisible || inline_diff.by_characters(x, y);
+                let (left, right) = highlights(x, y, by_characters);
+                DiffLine {
+                    text: row(&left, &right, '|', Some(Red), Some(Green), column_width),
+                    is_change: true,
+                    old_lines: 1,
+                    new_lines: 1,
+                }
+            }
+            (Some(x), None) => DiffLine {
+                text: row(&plain(x), &Vec::new(), '<', Some(Red), None, column_width),
+                is_change: true,
+                old_lines: 1,
+                new_lines: 0,
+            },
+            (None, Some(y)) => DiffLine {
+                text: row(&Vec::new(), &plain(y), '>', None, Some(Green), column_width),
+                is_change: true,
+                old_lines: 0,
+                new_lines: 1,
+            },
+            (None, None) => unreachable!()
+        };
+        lines.push(line);
+    }
+
+    removed.clear();
+    added.clear();
+}
+
+fn plain(line: &str) -> Highlighted {
+    line.chars().map(|c| (c, false)).collect()
+}
+
+/// Highlight the characters, or the words, of each line which are not found in the other line.
+fn highlights(x: &str, y: &str, by_characters: bool) -> (Highlighted, Highlighted) {
+    if by_characters {
+        let old = x.chars().collect::<Vec<_>>();
+        let new = y.chars().collect::<Vec<_>>();
+        let mut left = old.iter().map(|&c| (c, true)).collect::<Vec<_>>();
+        let mut right = new.iter().map(|&c| (c, true)).collect::<Vec<_>>();
+        for edit in diff_sequences(&old, &new) {
+            if let Edit::Same(i, j) = edit {
+                left[i].1 = false;
+                right[j].1 = false;
+            }
+        }
+        (left, right)
+    } else {
+        let old = x.split(' ').collect::<Vec<_>>();
+        let new = y.split(' ').collect::<Vec<_>>();
+        let mut old_same = vec![false; old.len()];
+        let mut new_same = vec![false; new.len()];
+        for edit in diff_sequences(&old, &new) {
+            if let Edit::Same(i, j) = edit {
+                old_same[i] = true;
+                new_same[j] = true;
+            }
+        }
+        (highlight_words(&old, &old_same), highlight_words(&new, &new_same))
+    }
+}
+
+fn highlight_words(words: &[&str], is_same: &[bool]) -> Highlighted {
+    let mut result = Vec::new();
+    for (index, word) in words.iter().enumerate() {
+        if index > 0 {
+            result.push((' ', false));
+        }
+        result.extend(word.chars().map(|c| (c, !is_same[index])));
+    }
+    result
+}
+
+/// Render a row with the given lines on each side, wrapping them if they do not fit their column.
+fn row(left: &[(char, bool)], right: &[(char, bool)], marker: char,
+       left_color: Option<Colour>, right_color: Option<Colour>, column_width: usize) -> String {
+    let left_chunks = wrap(left, column_width);
+    let right_chunks = wrap(right, column_width);
+    let row_count = cmp::max(cmp::max(left_chunks.len(), right_chunks.len()), 1);
+
+    (0..row_count).map(|index| {
+        let left_chunk = left_chunks.get(index).cloned().unwrap_or(&[]);
+        let right_chunk = right_chunks.get(index).cloned().unwrap_or(&[]);
+        let left_text = left_chunk.iter().map(|&(c, _)| c).collect::<String>();
+        let padding = " ".repeat(column_width.saturating_sub(display_width(&left_text)));
+        format!("{}{} {} {}", paint(left_chunk, left_color), padding, marker, paint(right_chunk, right_color))
+            .trim_end()
+            .to_owned()
+    }).collect::<Vec<_>>().join("\n")
+}
+
+/// Split a line into chunks fitting in the given number of columns, keeping zero-width
+/// characters, such as combining marks, with the character before them.
+fn wrap(line: &[(char, bool)], column_width: usize) -> Vec<&[(char, bool)]> {
+    let mut chunks = Vec::new();
+    let (mut start, mut width) = (0, 0);
+    for (index, &(c, _)) in line.iter().enumerate() {
+        let char_width = cmp::min(c.width().unwrap_or(0), 2);
+        if width + char_width > column_width && index > start {
+            chunks.push(&line[start..index]);
+            start = index;
+            width = 0;
+        }
+        width += char_width;
+    }
+    if start < line.len() {
+        chunks.push(&line[start..]);
+    }
+    chunks
+}
+
+fn paint(chunk: &[(char, bool)], color: Option<Colour>) -> String {
+    let color = match color {
+        Some(color) => color,
+        None => return chunk.iter().map(|&(c, _)| c).collect()
+    };
+
+    // consecutive characters with the same highlighting are painted together
+    let mut result = String::new();
+    let mut start = 0;
+    while start < chunk.len() {
+        let is_highlighted = chunk[start].1;
+        let end = chunk[start..].iter()
+            .position(|&(_, highlighted)| highlighted != is_highlighted)
+            .map(|offset| start + offset)
+            .unwrap_or(chunk.len());
+        let segment = chunk[start..end].iter().map(|&(c, _)| c).collect::<String>();
+        if is_highlighted {
+            result.push_str(&White.on(color).paint(segment).to_string());
+        } else {
+            result.push_str(&color.paint(segment).to_string());
+        }
+        start = end;
+    }
+    result
+}
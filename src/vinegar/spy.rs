@@ -0,0 +1,132 @@
+use std::cell::RefCell;
+use std::fmt::Debug;
+
+/// A wrapper around a closure that records the arguments of every call made to it.
+///
+/// Closures taking more than one argument can be spied on by taking a tuple of arguments.
+///
+/// The expectation methods, [`called_times`][called_times], [`called_with`][called_with] and
+/// [`never_called`][never_called], can be verified with [`check`][check], just like
+/// the expectation macros. When they fail, all recorded calls are listed in the error message.
+///
+/// [called_times]: struct.Spy.html#method.called_times
+/// [called_with]: struct.Spy.html#method.called_with
+/// [never_called]: struct.Spy.html#method.never_called
+/// [check]: fn.check.html
+///
+/// # Examples
+///
+/// ```
+/// use vinegar::vinegar::{check, Spy};
+///
+/// fn notify<F: Fn(&str, u32)>(callback: F) {
+///     callback("done", 100);
+/// }
+///
+/// let spy = Spy::new(|(_event, _progress): (String, u32)| ());
+///
+/// notify(|event, progress| spy.call((event.to_owned(), progress)));
+///
+/// check(vec![
+///     spy.called_times(1),
+///     spy.called_with(&("done".to_owned(), 100)),
+/// ]);
+/// ```
+pub struct Spy<A, R> {
+    name: String,
+    function: RefCell<Box<dyn FnMut(A) -> R>>,
+    calls: RefCell<Vec<A>>,
+}
+
+impl<A, R> Spy<A, R> where A: Clone + Debug + PartialEq {
+    /// Create a spy wrapping the given closure.
+    pub fn new<F>(function: F) -> Spy<A, R> where F: FnMut(A) -> R + 'static {
+        Spy::named("Spy", function)
+    }
+
+    /// Create a spy wrapping the given closure, using the given name in error messages.
+    pub fn named<F>(name: &str, function: F) -> Spy<A, R> where F: FnMut(A) -> R + 'static {
+        Spy {
+            name: name.to_owned(),
+            function: RefCell::new(Box::new(function)),
+            calls: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Replace the wrapped closure, so that subsequent calls are delegated to the given closure.
+    ///
+    /// Calls recorded so far are kept.
+    pub fn will<F>(&self, function: F) where F: FnMut(A) -> R + 'static {
+        *self.function.borrow_mut() = Box::new(function);
+    }
+
+    /// Make all subsequent calls return a clone of the given value.
+    pub fn returns(&self, value: R) where R: Clone + 'static {
+        self.will(move |_| value.clone());
+    }
+
+    /// Call the wrapped closure, recording the given arguments.
+    pub fn call(&self, args: A) -> R {
+        self.calls.borrow_mut().push(args.clone());
+        (*self.function.borrow_mut())(args)
+    }
+
+    /// The arguments of all calls made so far, in the order they were made.
+    pub fn calls(&self) -> Vec<A> {
+        self.calls.borrow().clone()
+    }
+
+    /// The number of calls made so far.
+    pub fn call_count(&self) -> usize {
+        self.calls.borrow().len()
+    }
+
+    /// Expect the spy to have been called exactly `times` times.
+    pub fn called_times(&self, times: usize) -> Result<(), String> {
+        let count = self.call_count();
+        if count == times {
+            Ok(())
+        } else {
+            Err(format!("* {} expected to be called {}, but was called {}.\n{}",
+                        self.name, times_text(times), times_text(count), self.recorded_calls()))
+        }
+    }
+
+    /// Expect the spy to have been called at least once with the given arguments.
+    pub fn called_with(&self, args: &A) -> Result<(), String> {
+        if self.calls.borrow().contains(args) {
+            Ok(())
+        } else {
+            Err(format!("* {} expected to be called with {:?}, but was not.\n{}",
+                        self.name, args, self.recorded_calls()))
+        }
+    }
+
+    /// Expect the spy to have never been called.
+    pub fn never_called(&self) -> Result<(), String> {
+        let count = self.call_count();
+        if count == 0 {
+            Ok(())
+        } else {
+            Err(format!("* {} expected to never be called, but was called {}.\n{}",
+                        self.name, times_text(count), self.recorded_calls()))
+        }
+    }
+
+    fn recorded_calls(&self) -> String {
+        let calls = self.calls.borrow();
+        if calls.is_empty() {
+            "  No calls were recorded.\n".to_owned()
+        } else {
+            let lines = calls.iter().enumerate()
+                .map(|(index, args)| format!("    call[{}]: {:?}\n", index, args))
+                .collect::<Vec<_>>()
+                .join("");
+            format!("  Recorded calls:\n{}", lines)
+        }
+    }
+}
+
+fn times_text(times: usize) -> String {
+    format!("{} time{}", times, if times == 1 { "" } else { "s" })
+}
@@ -0,0 +1,66 @@
+//! Procedural macros for the [`vinegar`](https://docs.rs/vinegar) crate.
+//!
+//! These macros are re-exported by `vinegar`, so this crate should not normally be used directly.
+
+#![crate_name = "vinegar_macros"]
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+mod power;
+mod text;
+
+use proc_macro::TokenStream;
+use proc_macro2::{TokenStream as TokenStream2, TokenTree};
+
+/// Create an expectation from any boolean expression which, if it fails, shows the value of
+/// every sub-expression of the condition, power-assert style.
+///
+/// The expression is evaluated only once, and `&&` and `||` keep their short-circuit semantics:
+/// sub-expressions which are not evaluated have no value shown. If the condition is made of
+/// several clauses joined by `&&` or `||`, the result of each clause is also listed, showing
+/// which clauses failed and which were not evaluated.
+///
+/// Values are shown with their `Debug` implementation, or with their type name if they do not
+/// implement `Debug`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate vinegar;
+/// # extern crate vinegar_macros;
+/// # fn main() {
+/// use vinegar::vinegar::check;
+/// use vinegar_macros::expect;
+///
+/// let items = vec![1, 2, 3];
+/// check(vec![
+///     expect!(items.len() + 1 > 3),
+///     expect!(items.contains(&2) && !items.is_empty()),
+/// ]);
+/// # }
+/// ```
+#[proc_macro]
+pub fn expect(input: TokenStream) -> TokenStream {
+    power::expect(quote!(::vinegar::vinegar), input.into()).into()
+}
+
+/// Same as `expect`, but the path to the `vinegar` module is given before the expression,
+/// followed by `;`, so that `vinegar` macros can pass `$crate::vinegar`.
+#[doc(hidden)]
+#[proc_macro]
+pub fn internal_power_expect(input: TokenStream) -> TokenStream {
+    let mut tokens = TokenStream2::from(input).into_iter();
+    let path = tokens.by_ref()
+        .take_while(|token| match *token {
+            TokenTree::Punct(ref punct) => punct.as_char() != ';',
+            _ => true
+        })
+        .collect::<TokenStream2>();
+    power::expect(path, tokens.collect()).into()
+}
@@ -0,0 +1,383 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::ToTokens;
+use syn::parse::{ParseStream, Parser};
+use syn::{BinOp, Expr, ExprClosure, Pat, Token, UnOp};
+
+use text::tokens_text;
+
+/// Generate an expectation that records the values of the sub-expressions of the given condition
+/// while it is evaluated, so they can be shown if it fails.
+///
+/// The condition may be followed by `format!`-style arguments for a custom message, which are
+/// only evaluated if it fails.
+pub fn expect(vinegar: TokenStream, input: TokenStream) -> TokenStream {
+    let (condition, message) = match parse_input.parse2(input) {
+        Ok(input) => input,
+        Err(err) => return err.to_compile_error()
+    };
+
+    let mut renderer = Renderer {
+        text: String::new(),
+        vinegar: vinegar.clone(),
+        values: Ident::new("values", Span::mixed_site()),
+        value: Ident::new("value", Span::mixed_site()),
+        clauses: Ident::new("clauses", Span::mixed_site()),
+        clause_ranges: Vec::new(),
+    };
+    let condition = match chain_operator(&condition) {
+        Some(op) => renderer.render_chain(&condition, op),
+        None => renderer.render(&condition)
+    };
+    let condition = renderer.flush_by_value(condition);
+    let (text, values, clauses) = (&renderer.text, &renderer.values, &renderer.clauses);
+    let clause_count = renderer.clause_ranges.len();
+    let clause_results = renderer.clause_ranges.iter().enumerate()
+        .map(|(index, &(start, end))| quote!((#start, #end, #clauses[#index])));
+
+    let error = quote!(#vinegar::internal_power_build_error(#text, &#values, &[#(#clause_results),*]));
+    let error = match message {
+        Some(message) => quote!(#vinegar::internal_with_message(#error, format!(#message))),
+        None => error
+    };
+
+    quote!({
+        use #vinegar::{InternalDebugValue as _, InternalOtherValue as _};
+        #[allow(unused_mut)]
+        let mut #values: ::std::vec::Vec<(usize, ::std::string::String)> = ::std::vec::Vec::new();
+        #[allow(unused_mut)]
+        let mut #clauses: [::std::option::Option<bool>; #clause_count] = [::std::option::Option::None; #clause_count];
+        if #condition {
+            ::std::result::Result::Ok(())
+        } else {
+            ::std::result::Result::Err(#error)
+        }
+    })
+}
+
+/// Parse the condition, and the arguments of the custom message if there are any.
+fn parse_input(input: ParseStream) -> syn::Result<(Expr, Option<TokenStream>)> {
+    let condition = input.parse()?;
+    if input.is_empty() {
+        return Ok((condition, None));
+    }
+    input.parse::<Token![,]>()?;
+    let message = input.parse::<TokenStream>()?;
+    Ok((condition, if message.is_empty() { None } else { Some(message) }))
+}
+
+fn is_same_chain(op: &BinOp, chain_op: &BinOp) -> bool {
+    matches!((op, chain_op), (BinOp::And(_), BinOp::And(_)) | (BinOp::Or(_), BinOp::Or(_)))
+}
+
+/// The operator of a condition made of several clauses joined by `&&` or `||`.
+fn chain_operator(expr: &Expr) -> Option<&BinOp> {
+    match *expr {
+        Expr::Binary(ref binary) => match binary.op {
+            BinOp::And(_) | BinOp::Or(_) => Some(&binary.op),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// An expression rewritten to record the values of its sub-expressions.
+struct Instrumented {
+    tokens: TokenStream,
+    /// Statements recording the values of place expressions (such as variables and fields),
+    /// which must run before `tokens` is evaluated.
+    ///
+    /// Place expressions are recorded by reference, instead of being rewritten, so that they can
+    /// still be borrowed, mutated or moved as in the original expression.
+    records: Vec<TokenStream>,
+}
+
+struct Renderer {
+    /// The source text of the condition rendered so far.
+    text: String,
+    vinegar: TokenStream,
+    values: Ident,
+    value: Ident,
+    clauses: Ident,
+    /// The start and end offsets in `text` of each clause of a condition joined by `&&` or `||`.
+    clause_ranges: Vec<(usize, usize)>,
+}
+
+impl Renderer {
+    /// Render the given expression, appending its source to `text` and recording the offset in
+    /// `text` under which the value of each sub-expression is shown.
+    fn render(&mut self, expr: &Expr) -> Instrumented {
+        match *expr {
+            Expr::Binary(ref binary) => {
+                let left = self.render(&binary.left);
+                self.text.push(' ');
+                let offset = self.text.len();
+                self.text.push_str(&tokens_text(binary.op.to_token_stream()));
+                self.text.push(' ');
+                let right = self.render(&binary.right);
+                let op = &binary.op;
+                match *op {
+                    // the right side must only be recorded if it is evaluated
+                    BinOp::And(_) | BinOp::Or(_) => {
+                        let (left, right) = (self.flush_by_value(left), self.flush_by_value(right));
+                        self.record_value(offset, quote!(#left #op #right), Vec::new())
+                    }
+                    // comparisons take their operands by reference, which must not be moved
+                    BinOp::Eq(_) | BinOp::Ne(_) | BinOp::Lt(_) | BinOp::Le(_) | BinOp::Gt(_) | BinOp::Ge(_) => {
+                        let (left, right) = (self.flush_by_ref(left), self.flush_by_ref(right));
+                        self.record_value(offset, quote!(#left #op #right), Vec::new())
+                    }
+                    _ => {
+                        let (left_tokens, right_tokens) = (&left.tokens, &right.tokens);
+                        let tokens = quote!(#left_tokens #op #right_tokens);
+                        self.record_value(offset, tokens, concat(left.records, right.records))
+                    }
+                }
+            }
+            Expr::Unary(ref unary) => {
+                let offset = self.text.len();
+                self.text.push_str(&tokens_text(unary.op.to_token_stream()));
+                let operand = self.render(&unary.expr);
+                let (op, operand_tokens) = (&unary.op, &operand.tokens);
+                let tokens = quote!(#op #operand_tokens);
+                match (unary.op, &*unary.expr) {
+                    (UnOp::Deref(_), operand_expr) if is_simple(operand_expr) => {
+                        self.record_place(offset, tokens, operand.records)
+                    }
+                    (UnOp::Deref(_), _) => Instrumented { tokens, records: operand.records },
+                    (UnOp::Neg(_), &Expr::Lit(_)) => Instrumented { tokens, records: operand.records },
+                    _ => self.record_value(offset, tokens, operand.records)
+                }
+            }
+            Expr::MethodCall(ref call) => {
+                let receiver = self.render(&call.receiver);
+                self.text.push('.');
+                let offset = self.text.len();
+                self.text.push_str(&call.method.to_string());
+                if let Some(ref turbofish) = call.turbofish {
+                    self.text.push_str("::");
+                    self.text.push_str(&tokens_text(turbofish.to_token_stream()));
+                }
+                let (args, records) = self.render_args(call.args.iter(), receiver.records);
+                let (receiver, method, turbofish) = (&receiver.tokens, &call.method, &call.turbofish);
+                self.record_value(offset, quote!(#receiver.#method #turbofish(#(#args),*)), records)
+            }
+            Expr::Call(ref call) => {
+                let offset = self.text.len();
+                self.text.push_str(&tokens_text(call.func.to_token_stream()));
+                let (args, records) = self.render_args(call.args.iter(), Vec::new());
+                let func = &call.func;
+                self.record_value(offset, quote!(#func(#(#args),*)), records)
+            }
+            Expr::Field(ref field) => {
+                let base = self.render(&field.base);
+                self.text.push('.');
+                let offset = self.text.len();
+                self.text.push_str(&tokens_text(field.member.to_token_stream()));
+                let (base_tokens, member) = (&base.tokens, &field.member);
+                let tokens = quote!(#base_tokens.#member);
+                if is_simple(&field.base) {
+                    self.record_place(offset, tokens, base.records)
+                } else {
+                    // recording the field would evaluate its base again
+                    Instrumented { tokens, records: base.records }
+                }
+            }
+            Expr::Index(ref index) => {
+                let base = self.render(&index.expr);
+                let offset = self.text.len();
+                self.text.push('[');
+                let position = self.render(&index.index);
+                self.text.push(']');
+                let base_tokens = &base.tokens;
+                if is_simple(&index.expr) && is_simple(&index.index) {
+                    let position_tokens = &position.tokens;
+                    let tokens = quote!(#base_tokens[#position_tokens]);
+                    self.record_place(offset, tokens, concat(base.records, position.records))
+                } else {
+                    // the index or its base may have side effects, so they must not be evaluated twice
+                    let position = self.flush_by_value(position);
+                    Instrumented { tokens: quote!(#base_tokens[#position]), records: base.records }
+                }
+            }
+            Expr::Path(ref path) => {
+                let offset = self.text.len();
+                let tokens = path.to_token_stream();
+                self.text.push_str(&tokens_text(tokens.clone()));
+                let is_constant = path.qself.is_none() && path.path.segments.last()
+                    .is_some_and(|segment| segment.ident.to_string().starts_with(char::is_uppercase));
+                if is_constant {
+                    Instrumented { tokens, records: Vec::new() }
+                } else {
+                    self.record_place(offset, tokens, Vec::new())
+                }
+            }
+            Expr::Paren(ref paren) => {
+                self.text.push('(');
+                let inner = self.render(&paren.expr);
+                self.text.push(')');
+                let inner_tokens = &inner.tokens;
+                Instrumented { tokens: quote!((#inner_tokens)), records: inner.records }
+            }
+            Expr::Group(ref group) => self.render(&group.expr),
+            Expr::Reference(ref reference) => {
+                self.text.push('&');
+                if reference.mutability.is_some() {
+                    self.text.push_str("mut ");
+                }
+                let inner = self.render(&reference.expr);
+                let (mutability, inner_tokens) = (&reference.mutability, &inner.tokens);
+                Instrumented { tokens: quote!(&#mutability #inner_tokens), records: inner.records }
+            }
+            Expr::Cast(ref cast) => {
+                let inner = self.render(&cast.expr);
+                self.text.push(' ');
+                let offset = self.text.len();
+                self.text.push_str("as ");
+                self.text.push_str(&tokens_text(cast.ty.to_token_stream()));
+                let (inner_tokens, ty) = (&inner.tokens, &cast.ty);
+                self.record_value(offset, quote!(#inner_tokens as #ty), inner.records)
+            }
+            Expr::Macro(ref mac) => {
+                let offset = self.text.len();
+                self.text.push_str(&tokens_text(mac.to_token_stream()));
+                self.record_value(offset, mac.to_token_stream(), Vec::new())
+            }
+            Expr::Closure(ref closure) => {
+                self.text.push_str(&closure_text(closure));
+                Instrumented { tokens: closure.to_token_stream(), records: Vec::new() }
+            }
+            // other expressions, such as literals and blocks, are shown without values
+            ref other => {
+                self.text.push_str(&tokens_text(other.to_token_stream()));
+                Instrumented { tokens: other.to_token_stream(), records: Vec::new() }
+            }
+        }
+    }
+
+    /// Render a chain of clauses joined by the given operator, recording the result of each
+    /// clause which is evaluated.
+    fn render_chain(&mut self, expr: &Expr, chain_op: &BinOp) -> Instrumented {
+        match *expr {
+            Expr::Binary(ref binary) if is_same_chain(&binary.op, chain_op) => {
+                let left = self.render_chain(&binary.left, chain_op);
+                let left = self.flush_by_value(left);
+                self.text.push(' ');
+                let offset = self.text.len();
+                self.text.push_str(&tokens_text(binary.op.to_token_stream()));
+                self.text.push(' ');
+                let right = self.render_chain(&binary.right, chain_op);
+                let right = self.flush_by_value(right);
+                let op = &binary.op;
+                self.record_value(offset, quote!(#left #op #right), Vec::new())
+            }
+            _ => {
+                let start = self.text.len();
+                let clause = self.render(expr);
+                let clause = self.flush_by_value(clause);
+                let index = self.clause_ranges.len();
+                self.clause_ranges.push((start, self.text.len()));
+                let (clauses, value) = (&self.clauses, &self.value);
+                Instrumented {
+                    tokens: quote!(match #clause {
+                        #value => {
+                            #clauses[#index] = ::std::option::Option::Some(#value);
+                            #value
+                        }
+                    }),
+                    records: Vec::new(),
+                }
+            }
+        }
+    }
+
+    fn render_args<'a, I>(&mut self, args: I, mut records: Vec<TokenStream>) -> (Vec<TokenStream>, Vec<TokenStream>)
+        where I: Iterator<Item=&'a Expr> {
+        self.text.push('(');
+        let mut tokens = Vec::new();
+        for (index, arg) in args.enumerate() {
+            if index > 0 {
+                self.text.push_str(", ");
+            }
+            let arg = self.render(arg);
+            records.extend(arg.records);
+            tokens.push(arg.tokens);
+        }
+        self.text.push(')');
+        (tokens, records)
+    }
+
+    /// Evaluate an expression, recording its value once evaluated.
+    fn record_value(&self, offset: usize, tokens: TokenStream, records: Vec<TokenStream>) -> Instrumented {
+        let (vinegar, values, value) = (&self.vinegar, &self.values, &self.value);
+        Instrumented {
+            tokens: quote!(match ({ #(#records;)* }, #tokens) {
+                (_, #value) => {
+                    #values.push((#offset, (&#vinegar::InternalValue(&#value)).internal_describe()));
+                    #value
+                }
+            }),
+            records: Vec::new(),
+        }
+    }
+
+    /// Record the value of a place expression by reference, before it is used.
+    fn record_place(&self, offset: usize, tokens: TokenStream, mut records: Vec<TokenStream>) -> Instrumented {
+        let (vinegar, values) = (&self.vinegar, &self.values);
+        records.push(quote!(#values.push((#offset, (&#vinegar::InternalValue(&#tokens)).internal_describe()))));
+        Instrumented { tokens, records }
+    }
+
+    /// Run the pending records of an expression just before it is evaluated, moving its value.
+    fn flush_by_value(&self, instrumented: Instrumented) -> TokenStream {
+        let (records, tokens, value) = (&instrumented.records, &instrumented.tokens, &self.value);
+        if records.is_empty() {
+            instrumented.tokens
+        } else {
+            quote!(match ({ #(#records;)* }, #tokens) { (_, #value) => #value })
+        }
+    }
+
+    /// Run the pending records of an expression just before it is evaluated, keeping it a place
+    /// expression so that it can be borrowed.
+    fn flush_by_ref(&self, instrumented: Instrumented) -> TokenStream {
+        let (records, tokens, value) = (&instrumented.records, &instrumented.tokens, &self.value);
+        if records.is_empty() {
+            instrumented.tokens
+        } else {
+            quote!((*match ({ #(#records;)* }, &#tokens) { (_, #value) => #value }))
+        }
+    }
+}
+
+/// Whether an expression can be evaluated again without side effects, which is the case of
+/// literals, variables and their fields.
+fn is_simple(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Lit(_) | Expr::Path(_) => true,
+        Expr::Field(ref field) => is_simple(&field.base),
+        Expr::Index(ref index) => is_simple(&index.expr) && is_simple(&index.index),
+        Expr::Unary(ref unary) => matches!(unary.op, UnOp::Deref(_)) && is_simple(&unary.expr),
+        Expr::Paren(ref paren) => is_simple(&paren.expr),
+        Expr::Group(ref group) => is_simple(&group.expr),
+        Expr::Reference(ref reference) => is_simple(&reference.expr),
+        _ => false
+    }
+}
+
+fn closure_text(closure: &ExprClosure) -> String {
+    let inputs = closure.inputs.iter()
+        .map(|input: &Pat| tokens_text(input.to_token_stream()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let output = match closure.output {
+        syn::ReturnType::Default => String::new(),
+        syn::ReturnType::Type(_, ref ty) => format!(" -> {}", tokens_text(ty.to_token_stream()))
+    };
+    format!("{}|{}|{} {}", if closure.capture.is_some() { "move " } else { "" },
+            inputs, output, tokens_text(closure.body.to_token_stream()))
+}
+
+fn concat(mut first: Vec<TokenStream>, second: Vec<TokenStream>) -> Vec<TokenStream> {
+    first.extend(second);
+    first
+}
@@ -0,0 +1,98 @@
+use proc_macro2::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Render tokens as source code, with spacing close to how they would normally be written.
+pub fn tokens_text(tokens: TokenStream) -> String {
+    let mut result = String::new();
+    let mut previous = Previous::Start;
+    let mut tokens = tokens.into_iter().peekable();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Punct(punct) => {
+                // consecutive joint characters form a single operator, such as `::` or `==`
+                let mut op = punct.as_char().to_string();
+                let mut spacing = punct.spacing();
+                while spacing == Spacing::Joint {
+                    match tokens.peek() {
+                        Some(TokenTree::Punct(next)) => {
+                            op.push(next.as_char());
+                            spacing = next.spacing();
+                        }
+                        _ => break
+                    }
+                    tokens.next();
+                }
+                let is_unary = matches!(previous, Previous::Start | Previous::Op(_));
+                let is_macro_bang = op == "!" && previous == Previous::Ident
+                    && matches!(tokens.peek(), Some(TokenTree::Group(_)));
+                let (space_before, space_after) = match op.as_str() {
+                    "." | "::" => (false, false),
+                    "," | ";" | ":" => (false, true),
+                    "?" => (false, true),
+                    "!" if is_macro_bang => (false, false),
+                    "!" | "&" | "&&" | "*" | "-" if is_unary => (true, false),
+                    _ => (true, true)
+                };
+                push_separated(&mut result, &previous, space_before);
+                result.push_str(&op);
+                previous = if is_macro_bang { Previous::MacroBang } else { Previous::Op(space_after) };
+            }
+            TokenTree::Group(group) => {
+                let inner = tokens_text(group.stream());
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", "")
+                };
+                let is_call = matches!(previous, Previous::Ident | Previous::Close | Previous::MacroBang | Previous::Literal);
+                let space_before = group.delimiter() == Delimiter::Brace || !is_call;
+                push_separated(&mut result, &previous, space_before);
+                result.push_str(open);
+                if group.delimiter() == Delimiter::Brace && !inner.is_empty() {
+                    result.push(' ');
+                    result.push_str(&inner);
+                    result.push(' ');
+                } else {
+                    result.push_str(&inner);
+                }
+                result.push_str(close);
+                previous = Previous::Close;
+            }
+            TokenTree::Ident(ident) => {
+                push_separated(&mut result, &previous, true);
+                result.push_str(&ident.to_string());
+                previous = Previous::Ident;
+            }
+            TokenTree::Literal(literal) => {
+                push_separated(&mut result, &previous, true);
+                result.push_str(&literal.to_string());
+                previous = Previous::Literal;
+            }
+        }
+    }
+
+    result
+}
+
+#[derive(PartialEq)]
+enum Previous {
+    Start,
+    /// An operator, and whether it wants a space after it.
+    Op(bool),
+    MacroBang,
+    Ident,
+    Literal,
+    Close,
+}
+
+fn push_separated(result: &mut String, previous: &Previous, space_before: bool) {
+    let space = match *previous {
+        Previous::Start | Previous::MacroBang => false,
+        Previous::Op(space_after) => space_after && space_before,
+        _ => space_before
+    };
+    if space {
+        result.push(' ');
+    }
+}